@@ -0,0 +1,38 @@
+//! Expansion time and size of the macro, which proc-macro crates cannot
+//! export, so the expansion code is included directly.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+
+#[allow(dead_code)]
+#[path = "../src/expand.rs"]
+mod expand;
+
+fn input() -> TokenStream {
+    let fields = (0..64usize).map(|bit| {
+        let ident = format_ident!("f{bit}");
+        quote! { #ident: bool @ #bit, }
+    });
+    quote! {
+        #[derive(Clone, Copy, Debug)]
+        pub struct Wide: u64 {
+            #(#fields)*
+        }
+    }
+}
+
+fn bench(c: &mut Criterion) {
+    let input = input();
+    let size = expand::make_bitfield(input.clone())
+        .unwrap()
+        .to_string()
+        .len();
+    println!("expansion of 64 fields: {size} bytes");
+    c.bench_function("expand 64 fields", |b| {
+        b.iter(|| expand::make_bitfield(input.clone()).unwrap())
+    });
+}
+
+criterion_group!(benches, bench);
+criterion_main!(benches);
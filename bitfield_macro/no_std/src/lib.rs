@@ -0,0 +1,117 @@
+//! Compiles the macro output without `std`, with every option which does not
+//! need it.
+
+#![no_std]
+
+use bitfield_macro::{bitfield, bitfield_struct, FieldValue};
+
+#[derive(FieldValue, Clone, Copy, Debug, PartialEq)]
+pub enum Mode {
+    Off,
+    Low,
+    High,
+}
+
+bitfield! {
+    /// Control register.
+    #[bitfield(builder, debug, describe, diff, exhaustive, mmio)]
+    #[derive(Clone, Copy, PartialEq)]
+    pub struct Control: u32 {
+        pub enable: bool @ 0,
+        pub mode: Option<Mode> @ 1..3,
+        pub level: i8 @ 3..8,
+        pub prio: [u8; 4] @ 8..24,
+        #[ro]
+        pub id: u8 @ 24..28 => |v| v + 1,
+        #[w1c]
+        pub pending: u8 @ 28..32 => decode |v| v * 2, encode |v| v / 2,
+    }
+
+    #[bitfield(big_endian)]
+    pub struct Header(pub [u8; 3]) {
+        pub kind: u8 @ 0..4,
+        pub control: Flags @ 4..12,
+        pub length: u16 @ 12; 12,
+    }
+
+    #[derive(Clone, Copy)]
+    pub struct Flags: u8 {
+        pub flags: [bool; 4] @ 0..4,
+    }
+}
+
+#[bitfield_struct(u16)]
+pub struct Status {
+    #[bits(0..4)]
+    pub code: u8,
+    #[bits(4..16)]
+    pub count: u16,
+}
+
+pub const CONTROL: Control = Control::builder()
+    .enable(true)
+    .level(-2)
+    .prio([1, 2, 3, 4])
+    .build();
+
+pub fn checked(header: &mut Header, buffer: &mut [u8]) -> Option<u8> {
+    header.checked_set_byte(1, 0xFF).ok()?;
+    header.try_write_to(buffer).ok()?;
+    let flags = Flags::try_read_from(buffer)?;
+    flags.checked_byte(0)
+}
+
+/// Shadows the prelude, which the expansion must not rely on.
+#[allow(dead_code, unused_macros)]
+mod shadowed {
+    use bitfield_macro::{bitfield, FieldValue};
+
+    struct Option;
+    struct Some;
+    struct None;
+    struct Result;
+    struct Ok;
+    struct Err;
+    trait Iterator {}
+    trait From {}
+    trait FnOnce {}
+
+    macro_rules! assert {
+        ($($tokens:tt)*) => {};
+    }
+    macro_rules! unreachable {
+        ($($tokens:tt)*) => {};
+    }
+    macro_rules! format_args {
+        ($($tokens:tt)*) => {};
+    }
+
+    #[derive(FieldValue, Debug)]
+    enum Mode {
+        Off,
+        On,
+        Auto,
+    }
+
+    #[derive(FieldValue, Debug)]
+    enum Speed {
+        Slow,
+        Fast,
+    }
+
+    bitfield! {
+        #[bitfield(builder, debug, describe, diff, mmio)]
+        struct Control: u16 {
+            mode: core::option::Option<Mode> @ 0..2,
+            speed: Speed @ 2,
+            prio: [u8; 2] @ 4..8,
+            #[w1c]
+            pending: bool @ 8,
+        }
+
+        #[bitfield(big_endian)]
+        struct Header: [u8; 2] {
+            control: Control @ 0..16,
+        }
+    }
+}
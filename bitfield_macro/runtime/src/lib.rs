@@ -0,0 +1,346 @@
+#![no_std]
+
+use core::fmt::{self, Write};
+use core::ops::Range;
+
+/// Conversion between a typed bitfield field value and its raw bits.
+///
+/// Implement it by hand or use `#[derive(FieldValue)]` from `bitfield_macro`
+/// on a fieldless enum. Bitfields implement it to be nested in other
+/// bitfields.
+pub trait FieldValue: Sized {
+    /// Number of bits needed to store every value.
+    const BITS: usize;
+
+    /// Whether every bit pattern of width `BITS` maps to a value.
+    const EXHAUSTIVE: bool;
+
+    fn from_bits(bits: u128) -> Option<Self>;
+
+    fn into_bits(self) -> u128;
+}
+
+/// Implemented by every `bitfield!` type, to handle bitfields generically.
+pub trait Bitfield: Sized {
+    /// Backing data, an unsigned integer or a byte array.
+    type Raw: Copy;
+
+    /// Bits of the backing data which are assigned to a field.
+    const MASK: Self::Raw;
+
+    /// Fields in declaration order.
+    const FIELDS: &'static [FieldInfo];
+
+    fn raw(&self) -> Self::Raw;
+
+    /// Creates a bitfield, clearing the bits outside of `MASK`.
+    fn from_raw(raw: Self::Raw) -> Self;
+}
+
+/// Description of a bitfield field.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FieldInfo {
+    pub name: &'static str,
+    /// Bits as declared, which count from the most significant bit for
+    /// big-endian bitfields.
+    pub range: Range<usize>,
+    pub access: Access,
+}
+
+/// How software may access a field.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Access {
+    ReadWrite,
+    ReadOnly,
+    WriteOnly,
+    /// Readable, and writing a one clears the bit.
+    WriteOneToClear,
+}
+
+impl Access {
+    /// Short name as used in register documentation.
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Access::ReadWrite => "rw",
+            Access::ReadOnly => "ro",
+            Access::WriteOnly => "wo",
+            Access::WriteOneToClear => "w1c",
+        }
+    }
+}
+
+/// Error of the checked bitfield methods, for an index or a buffer which is
+/// out of bounds.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OutOfBounds;
+
+impl fmt::Display for OutOfBounds {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("index out of bounds")
+    }
+}
+
+/// Change of a field between two bitfield values, from the `diff` generated
+/// with `#[bitfield(diff)]`.
+///
+/// Displays as `IRQ_ENABLE 0 -> 1`, with hexadecimal values for `{:#}`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FieldChange {
+    pub name: &'static str,
+    /// Entry of an array field.
+    pub index: Option<usize>,
+    pub old: ChangeValue,
+    pub new: ChangeValue,
+}
+
+/// Value of a changed field. Enum and piped fields report the bits stored
+/// in the bitfield.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChangeValue {
+    Unsigned(u128),
+    /// Sign-extended value of a signed field.
+    Signed(i128),
+}
+
+impl fmt::Display for ChangeValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            ChangeValue::Unsigned(value) if f.alternate() => write!(f, "{value:#x}"),
+            ChangeValue::Unsigned(value) => write!(f, "{value}"),
+            ChangeValue::Signed(value) if f.alternate() && value < 0 => {
+                write!(f, "-{:#x}", value.unsigned_abs())
+            }
+            ChangeValue::Signed(value) if f.alternate() => write!(f, "{value:#x}"),
+            ChangeValue::Signed(value) => write!(f, "{value}"),
+        }
+    }
+}
+
+impl fmt::Display for FieldChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for char in self.name.chars() {
+            f.write_char(char.to_ascii_uppercase())?;
+        }
+        if let Some(index) = self.index {
+            write!(f, "[{index}]")?;
+        }
+        if f.alternate() {
+            write!(f, " {:#} -> {:#}", self.old, self.new)
+        } else {
+            write!(f, " {} -> {}", self.old, self.new)
+        }
+    }
+}
+
+/// Layout of a bitfield with its documentation, generated by
+/// `#[bitfield(describe)]` as the `LAYOUT` constant.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Layout {
+    pub name: &'static str,
+    pub bits: usize,
+    /// Whether ranges count from the most significant bit.
+    pub big_endian: bool,
+    pub doc: &'static str,
+    pub fields: &'static [FieldLayout],
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FieldLayout {
+    pub info: FieldInfo,
+    pub doc: &'static str,
+}
+
+impl Layout {
+    /// Writes a Markdown table of the fields ordered by their bits, with rows
+    /// for reserved bits.
+    pub fn write_markdown(&self, w: &mut impl Write) -> fmt::Result {
+        writeln!(w, "## {}", self.name)?;
+        writeln!(w)?;
+        if !self.doc.is_empty() {
+            writeln!(w, "{}", self.doc)?;
+            writeln!(w)?;
+        }
+        let order = if self.big_endian { " (MSB 0)" } else { "" };
+        writeln!(w, "| Bits{order} | Field | Access | Description |")?;
+        writeln!(w, "|---|---|---|---|")?;
+
+        // Select fields in order instead of sorting them, which needs no
+        // allocation
+        let mut last = None;
+        let mut next = 0;
+        while let Some((key, field)) = self
+            .fields
+            .iter()
+            .enumerate()
+            .map(|(index, field)| ((field.info.range.start, index), field))
+            .filter(|(key, _)| last.is_none_or(|last| *key > last))
+            .min_by_key(|(key, _)| *key)
+        {
+            let range = &field.info.range;
+            if range.start > next {
+                writeln!(w, "| {next}..{} | | | Reserved |", range.start)?;
+            }
+            write!(w, "| {}..{} | ", range.start, range.end)?;
+            write_cell(w, field.info.name)?;
+            write!(w, " | {} | ", field.info.access.as_str())?;
+            write_cell(w, field.doc)?;
+            writeln!(w, " |")?;
+            next = next.max(range.end);
+            last = Some(key);
+        }
+        if next < self.bits {
+            writeln!(w, "| {next}..{} | | | Reserved |", self.bits)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the layout as a JSON object.
+    pub fn write_json(&self, w: &mut impl Write) -> fmt::Result {
+        w.write_str("{\"name\":")?;
+        write_string(w, self.name)?;
+        write!(
+            w,
+            ",\"bits\":{},\"big_endian\":{},\"doc\":",
+            self.bits, self.big_endian
+        )?;
+        write_string(w, self.doc)?;
+        w.write_str(",\"fields\":[")?;
+        for (index, field) in self.fields.iter().enumerate() {
+            if index > 0 {
+                w.write_char(',')?;
+            }
+            w.write_str("{\"name\":")?;
+            write_string(w, field.info.name)?;
+            write!(
+                w,
+                ",\"start\":{},\"end\":{},\"access\":\"{}\",\"doc\":",
+                field.info.range.start,
+                field.info.range.end,
+                field.info.access.as_str()
+            )?;
+            write_string(w, field.doc)?;
+            w.write_char('}')?;
+        }
+        w.write_str("]}")
+    }
+}
+
+/// Writes text into a table cell on a single line.
+fn write_cell(w: &mut impl Write, text: &str) -> fmt::Result {
+    for (index, line) in text.lines().enumerate() {
+        if index > 0 {
+            w.write_char(' ')?;
+        }
+        for (index, part) in line.split('|').enumerate() {
+            if index > 0 {
+                w.write_str("\\|")?;
+            }
+            w.write_str(part)?;
+        }
+    }
+    Ok(())
+}
+
+fn write_string(w: &mut impl Write, text: &str) -> fmt::Result {
+    w.write_char('"')?;
+    for char in text.chars() {
+        match char {
+            '"' => w.write_str("\\\"")?,
+            '\\' => w.write_str("\\\\")?,
+            '\n' => w.write_str("\\n")?,
+            char if (char as u32) < 0x20 => write!(w, "\\u{:04x}", char as u32)?,
+            char => w.write_char(char)?,
+        }
+    }
+    w.write_char('"')
+}
+
+/// Helpers shared by the generated code, which is not public API.
+#[doc(hidden)]
+pub mod __private {
+    /// Byte array backing data, where `big_endian` stores bit 0 in the last
+    /// byte.
+    pub mod bytes {
+        const fn index(len: usize, bit: usize, big_endian: bool) -> usize {
+            if big_endian {
+                len - 1 - bit / 8
+            } else {
+                bit / 8
+            }
+        }
+
+        pub const fn get(data: &[u8], shift: usize, len: usize, big_endian: bool) -> u128 {
+            let mut value = 0;
+            let mut offset = 0;
+            while offset < len {
+                let bit = shift + offset;
+                let byte = data[index(data.len(), bit, big_endian)];
+                value |= (((byte >> (bit % 8)) & 1) as u128) << offset;
+                offset += 1;
+            }
+            value
+        }
+
+        pub const fn set(data: &mut [u8], shift: usize, len: usize, value: u128, big_endian: bool) {
+            let mut offset = 0;
+            while offset < len {
+                let bit = shift + offset;
+                let byte = &mut data[index(data.len(), bit, big_endian)];
+                *byte &= !(1 << (bit % 8));
+                *byte |= (((value >> offset) & 1) as u8) << (bit % 8);
+                offset += 1;
+            }
+        }
+
+        pub const fn mask<const N: usize>(shift: usize, len: usize, big_endian: bool) -> [u8; N] {
+            let mut mask = [0; N];
+            set(&mut mask, shift, len, u128::MAX, big_endian);
+            mask
+        }
+
+        /// Writes the writable bits of a byte and clears its write-one-to-clear
+        /// bits which are set.
+        pub const fn write(data: &mut [u8], index: usize, byte: u8, writable: &[u8], clear: &[u8]) {
+            let data = &mut data[index];
+            *data = (*data & !writable[index]) | (byte & writable[index]);
+            *data &= !(byte & clear[index]);
+        }
+
+        pub const fn reverse<const N: usize>(bytes: [u8; N]) -> [u8; N] {
+            let mut reversed = [0; N];
+            let mut index = 0;
+            while index < N {
+                reversed[index] = bytes[N - 1 - index];
+                index += 1;
+            }
+            reversed
+        }
+    }
+
+    macro_rules! integers {
+        ($($ty:ident)*) => {$(
+            /// Integer backing data.
+            pub mod $ty {
+                const fn mask(len: usize) -> $ty {
+                    <$ty>::MAX >> (<$ty>::BITS as usize - len)
+                }
+
+                pub const fn get(data: $ty, shift: usize, len: usize) -> $ty {
+                    (data >> shift) & mask(len)
+                }
+
+                pub const fn set(data: $ty, shift: usize, len: usize, value: $ty) -> $ty {
+                    (data & !(mask(len) << shift)) | ((value & mask(len)) << shift)
+                }
+
+                /// Writes the writable bits and clears the write-one-to-clear
+                /// bits which are set.
+                pub const fn write(data: $ty, value: $ty, writable: $ty, clear: $ty) -> $ty {
+                    ((data & !writable) | (value & writable)) & !(value & clear)
+                }
+            }
+        )*};
+    }
+
+    integers!(u8 u16 u32 u64 u128 usize);
+}
@@ -0,0 +1,2436 @@
+use proc_macro2::TokenStream;
+use quote::*;
+use std::ops::Range;
+use syn::ext::IdentExt;
+use syn::parse::*;
+use syn::punctuated::Punctuated;
+use syn::spanned::Spanned;
+use syn::*;
+
+pub fn make_bitfield(input: TokenStream) -> Result<TokenStream> {
+    let bitfields = parse2::<Bitfields>(input)?;
+
+    let mut tokens = TokenStream::new();
+    let mut errors: Option<Error> = None;
+    for bitfield in bitfields.0.iter() {
+        match expand_bitfield(bitfield) {
+            Ok(bitfield) => tokens.extend(bitfield),
+            Err(error) => match &mut errors {
+                Some(errors) => errors.combine(error),
+                None => errors = Some(error),
+            },
+        }
+    }
+    errors.map_or(Ok(tokens), Err)
+}
+
+pub fn make_bitfield_struct(args: TokenStream, input: TokenStream) -> Result<TokenStream> {
+    let BitfieldArgs { ty, options } = parse2(args)?;
+    let mut item = parse2::<ItemStruct>(input)?;
+
+    if !item.generics.params.is_empty() {
+        return Err(Error::new_spanned(
+            &item.generics,
+            "Bitfield must not be generic",
+        ));
+    }
+
+    let named = match &item.fields {
+        Fields::Named(fields) => &fields.named,
+        _ => {
+            return Err(Error::new_spanned(
+                &item.fields,
+                "Bitfield expected named fields",
+            ))
+        }
+    };
+
+    let mut fields = Punctuated::new();
+    for field in named.iter() {
+        let mut attributes = field.attrs.clone();
+        let index = attributes
+            .iter()
+            .position(|attribute| attribute.path().is_ident("bits"));
+        let bits = index.map(|index| attributes.remove(index)).ok_or_else(|| {
+            Error::new_spanned(field, "Bitfield field expected #[bits(..)] attribute")
+        })?;
+
+        let (range, pipe) = bits.parse_args_with(|input: ParseStream| {
+            let range = input.parse()?;
+            let pipe = parse_pipe(input)?;
+            Ok((range, pipe))
+        })?;
+
+        fields.push(Field::new(
+            attributes,
+            field.vis.clone(),
+            field.ident.clone().unwrap(),
+            field.ty.clone(),
+            range,
+            pipe,
+        )?);
+    }
+
+    check_data_type(&ty)?;
+
+    let mut options = options;
+    item.attrs = options.extract(item.attrs)?;
+
+    expand_bitfield(&Bitfield {
+        attributes: item.attrs,
+        options,
+        visibility: item.vis,
+        ident: item.ident,
+        tuple: None,
+        ty,
+        fields,
+    })
+}
+
+fn expand_bitfield(bitfield: &Bitfield) -> Result<TokenStream> {
+    let member = bitfield.member();
+    let data_type = &bitfield.ty;
+    let raw_type = bitfield.raw_type();
+
+    let mut functions = vec![];
+    let mut checks = vec![];
+    let mut builder = vec![];
+    let mut accessors = vec![];
+    for field in bitfield.fields.iter() {
+        let field_type = &field.ty;
+
+        let bits = bitfield.bits();
+        let target_bits = bitfield.target_bits();
+        let count = field.count.unwrap_or(1);
+        let primitive = Primitive::of(field_type);
+        let max_len = primitive.map_or(128, Primitive::bits);
+        match &field.range.known {
+            Some(range) => {
+                if !(range.start < range.end
+                    && range.end <= bits
+                    && range.len() % count == 0
+                    && range.len() / count <= max_len)
+                {
+                    return Err(Error::new_spanned(
+                        &field.range,
+                        "Bitfield range is invalid",
+                    ));
+                }
+                if Primitive::is_pointer_sized(&bitfield.ty) {
+                    let end = range.end;
+                    checks.push(quote_spanned! { field.range.span() =>
+                        const _: () = ::core::assert!(
+                            #end <= <usize>::BITS as usize,
+                            "Bitfield range is wider than the data type on this target",
+                        );
+                    });
+                }
+            }
+            None => {
+                let FieldRange { start, end, .. } = &field.range;
+                let entries = field.count.map(|count| {
+                    quote! {
+                        && (end - start) % #count == 0
+                        && (end - start) / #count <= #max_len
+                    }
+                });
+                let entries = entries.unwrap_or_else(|| quote! { && end - start <= #max_len });
+                checks.push(quote_spanned! { field.range.span() =>
+                    const _: () = ::core::assert!(
+                        {
+                            let start: usize = #start;
+                            let end: usize = #end;
+                            start < end && end <= #target_bits #entries
+                        },
+                        "Bitfield range is invalid",
+                    );
+                });
+            }
+        }
+
+        let len = bitfield.width(field);
+
+        if Primitive::is_pointer_sized(field_type) {
+            checks.push(quote_spanned! { field_type.span() =>
+                const _: () = ::core::assert!(
+                    #len <= <#field_type>::BITS as usize,
+                    "Bitfield range is wider than the field type on this target",
+                );
+            });
+        }
+
+        let cast = match primitive {
+            Some(Primitive::Bool) => quote! {
+                let value = value != 0;
+            },
+            Some(Primitive::Unsigned(_)) => quote! {
+                let value = value as #field_type;
+            },
+            Some(Primitive::Signed(_)) => {
+                let extend = quote! { (<#field_type>::BITS as usize - #len) };
+                quote! {
+                    let value = ((value as #field_type) << #extend) >> #extend;
+                }
+            }
+            None => {
+                let value_type = field.value_type();
+                if option_type(field_type).is_none() {
+                    checks.push(quote_spanned! { field_type.span() =>
+                        const _: () = ::core::assert!(
+                            <#value_type as ::bitfield_runtime::FieldValue>::EXHAUSTIVE,
+                            "Bitfield field type does not cover every bit pattern, use an Option",
+                        );
+                        const _: () = ::core::assert!(
+                            <#value_type as ::bitfield_runtime::FieldValue>::BITS == #len,
+                            "Bitfield field type width does not match its range",
+                        );
+                    });
+                    quote! {
+                        let value = match <#value_type as ::bitfield_runtime::FieldValue>::from_bits(value as u128) {
+                            ::core::option::Option::Some(value) => value,
+                            ::core::option::Option::None => ::core::unreachable!(),
+                        };
+                    }
+                } else {
+                    checks.push(quote_spanned! { field_type.span() =>
+                        const _: () = ::core::assert!(
+                            <#value_type as ::bitfield_runtime::FieldValue>::BITS <= #len,
+                            "Bitfield field type does not fit into its range",
+                        );
+                    });
+                    quote! {
+                        let value = <#value_type as ::bitfield_runtime::FieldValue>::from_bits(value as u128);
+                    }
+                }
+            }
+        };
+
+        let into_raw = if primitive.is_some() {
+            quote! {
+                let value = value as #raw_type;
+            }
+        } else {
+            let value_type = field.value_type();
+            quote! {
+                let value = <#value_type as ::bitfield_runtime::FieldValue>::into_bits(value) as #raw_type;
+            }
+        };
+
+        // Pipes are coerced to function pointers to infer their argument types
+        let (decode, encode) = match &field.pipe {
+            Some(Pipe { decode, encode }) => (
+                Some(quote! {
+                    let decode: fn(#field_type) -> _ = #decode;
+                    let value = decode(value);
+                }),
+                encode.as_ref().map(|encode| {
+                    let return_type = field.return_type();
+                    let value_type = field.value_type();
+                    quote! {
+                        let encode: fn(#return_type) -> #value_type = #encode;
+                        let value = encode(value);
+                    }
+                }),
+            ),
+            None => (None, None),
+        };
+
+        let shift = bitfield.entry_shift(field);
+        let (index, check) = match field.count {
+            Some(count) => (
+                Some(quote! { index: usize, }),
+                Some(quote! { ::core::assert!(index < #count); }),
+            ),
+            None => (None, None),
+        };
+        let index_arg = index.as_ref().map(|_| quote! { index, });
+
+        let helpers = bitfield.helpers();
+        let (get, set) = if bitfield.bytes().is_some() {
+            let big_endian = bitfield.options.big_endian.is_some();
+            (
+                quote! {
+                    let value = #helpers::get(&self.#member, #shift, #len, #big_endian);
+                },
+                quote! {
+                    #helpers::set(&mut self.#member, #shift, #len, value, #big_endian);
+                },
+            )
+        } else {
+            (
+                quote! {
+                    let value = #helpers::get(self.#member, #shift, #len);
+                },
+                quote! {
+                    self.#member = #helpers::set(self.#member, #shift, #len, value);
+                },
+            )
+        };
+
+        accessors.push(Accessor {
+            field,
+            get: get.clone(),
+            cast: cast.clone(),
+            into_raw: into_raw.clone(),
+            set: set.clone(),
+        });
+
+        let attributes = &field.attributes;
+        let allow_deprecated = field.allow_deprecated();
+        let visibility = &field.visibility;
+        let ident = &field.ident;
+        let unraw = ident.unraw();
+        let ident_set = format_ident!("set_{unraw}");
+        let ident_with = format_ident!("with_{unraw}");
+        let return_type = field.return_type();
+        let input_type = field.input_type();
+
+        // Closures and trait methods cannot be called in const context
+        let constness = primitive.is_some().then(|| quote! { const });
+        let get_constness = decode.is_none().then(|| constness.clone()).flatten();
+        let set_constness = encode.is_none().then(|| constness.clone()).flatten();
+
+        if field.access.readable() {
+            functions.push(quote! {
+                #(#attributes)*
+                #visibility #get_constness fn #ident(&self, #index) -> #return_type {
+                    #check
+                    #get
+                    #cast
+                    #decode
+                    value
+                }
+            });
+        }
+
+        if field.access.writable() {
+            functions.push(quote! {
+                #(#attributes)*
+                #visibility #set_constness fn #ident_set(&mut self, #index value: #input_type) {
+                    #check
+                    #encode
+                    #into_raw
+                    #set
+                }
+
+                #(#attributes)*
+                #allow_deprecated
+                #visibility #set_constness fn #ident_with(mut self, #index value: #input_type) -> Self {
+                    self.#ident_set(#index_arg value);
+                    self
+                }
+            });
+
+            builder.push((field, set_constness.clone()));
+        }
+
+        if let Some(count) = field.count {
+            let ident_checked = format_ident!("checked_{unraw}");
+            let ident_checked_set = format_ident!("checked_set_{unraw}");
+            if field.access.readable() {
+                functions.push(quote! {
+                    #(#attributes)*
+                    #allow_deprecated
+                    #visibility #get_constness fn #ident_checked(
+                        &self,
+                        index: usize,
+                    ) -> ::core::option::Option<#return_type> {
+                        if index < #count {
+                            ::core::option::Option::Some(self.#ident(index))
+                        } else {
+                            ::core::option::Option::None
+                        }
+                    }
+                });
+            }
+            if field.access.writable() {
+                functions.push(quote! {
+                    #(#attributes)*
+                    #allow_deprecated
+                    #visibility #set_constness fn #ident_checked_set(
+                        &mut self,
+                        index: usize,
+                        value: #input_type,
+                    ) -> ::core::result::Result<(), ::bitfield_runtime::OutOfBounds> {
+                        if index < #count {
+                            self.#ident_set(index, value);
+                            ::core::result::Result::Ok(())
+                        } else {
+                            ::core::result::Result::Err(::bitfield_runtime::OutOfBounds)
+                        }
+                    }
+                });
+            }
+        }
+
+        if let (Some(count), true) = (field.count, field.access.readable()) {
+            let ident_iter = format_ident!("{unraw}_iter");
+            functions.push(quote! {
+                #(#attributes)*
+                #allow_deprecated
+                #visibility fn #ident_iter(&self) -> impl ::core::iter::Iterator<Item = #return_type> + '_ {
+                    (0..#count).map(move |index| self.#ident(index))
+                }
+            });
+        }
+
+        if field.access == Access::WriteOneToClear {
+            let ident_clear = format_ident!("clear_{unraw}");
+            functions.push(quote! {
+                #(#attributes)*
+                #visibility const fn #ident_clear(&mut self, #index) {
+                    #check
+                    let value: #raw_type = 0;
+                    #set
+                }
+            });
+        }
+    }
+
+    checks.extend(check_layout(bitfield)?);
+
+    let builder = bitfield
+        .options
+        .builder
+        .then(|| expand_builder(bitfield, builder));
+
+    let attributes = &bitfield.attributes;
+    let visibility = &bitfield.visibility;
+    let ident = &bitfield.ident;
+
+    let debug = bitfield.options.debug.then(|| expand_debug(bitfield));
+    let mmio = bitfield.options.mmio.then(|| expand_mmio(bitfield));
+    let layout = bitfield.options.describe.then(|| expand_layout(bitfield));
+
+    let field_value = expand_field_value(bitfield);
+    let bitfield_trait = expand_bitfield_trait(bitfield);
+    let operators = expand_operators(bitfield);
+    let bytes = expand_bytes(bitfield);
+    let diff = bitfield.options.diff.then(|| expand_diff(bitfield));
+    let constants = bitfield.fields.iter().map(|field| {
+        let name = field.ident.unraw().to_string().to_uppercase();
+        let mask = format_ident!("{name}_MASK");
+        let shift = format_ident!("{name}_SHIFT");
+        let bits = format_ident!("{name}_BITS");
+        let mask_value = bitfield.field_mask(field);
+        let shift_value = match bitfield.known_shift(field) {
+            Some(shift) => proc_macro2::Literal::usize_unsuffixed(shift).to_token_stream(),
+            None => {
+                let shift_value = bitfield.shift_value(field);
+                quote! {{
+                    let shift: usize = #shift_value;
+                    shift as u32
+                }}
+            }
+        };
+        let bits_value = match &field.range.known {
+            Some(range) => proc_macro2::Literal::usize_unsuffixed(range.len()).to_token_stream(),
+            None => {
+                let FieldRange { start, end, .. } = &field.range;
+                quote! {{
+                    let start: usize = #start;
+                    let end: usize = #end;
+                    end.saturating_sub(start) as u32
+                }}
+            }
+        };
+        let visibility = &field.visibility;
+        quote! {
+            #visibility const #mask: #data_type = #mask_value;
+            #visibility const #shift: u32 = #shift_value;
+            #visibility const #bits: u32 = #bits_value;
+        }
+    });
+    let serde = bitfield
+        .options
+        .serde
+        .map(|serde| expand_serde(bitfield, serde, &accessors));
+
+    let data_mask = bitfield.mask(|_| true);
+    let writable_mask = bitfield.mask(|field| field.access.writable());
+    let clear_mask = bitfield.mask(|field| field.access == Access::WriteOneToClear);
+
+    let helpers = bitfield.helpers();
+    let storage = match bitfield.bytes() {
+        Some(bytes) => {
+            let write_byte = quote! {
+                #helpers::write(
+                    &mut self.#member,
+                    index,
+                    byte,
+                    &Self::writable_mask(),
+                    &Self::clear_mask(),
+                )
+            };
+
+            quote! {
+                pub const fn new(data: #data_type) -> Self {
+                    let mut data = data;
+                    let mut index = 0;
+                    while index < #bytes {
+                        data[index] &= Self::data_mask()[index];
+                        index += 1;
+                    }
+                    Self { #member: data }
+                }
+
+                #visibility const fn data(&self) -> #data_type {
+                    self.#member
+                }
+
+                #visibility const fn set_data(&mut self, data: #data_type) {
+                    let mut index = 0;
+                    while index < #bytes {
+                        let byte = data[index];
+                        #write_byte;
+                        index += 1;
+                    }
+                }
+
+                #visibility const fn byte(&self, index: usize) -> u8 {
+                    ::core::assert!(index < #bytes);
+                    self.#member[index]
+                }
+
+                #visibility const fn set_byte(&mut self, index: usize, byte: u8) {
+                    ::core::assert!(index < #bytes);
+                    #write_byte;
+                }
+            }
+        }
+        None => {
+            let data_type_size = bitfield.size();
+
+            quote! {
+                pub const fn new(data: #data_type) -> Self {
+                    Self { #member: data & Self::data_mask() }
+                }
+
+                #visibility const fn data(&self) -> #data_type {
+                    self.#member
+                }
+
+                #visibility const fn set_data(&mut self, data: #data_type) {
+                    self.#member = #helpers::write(
+                        self.#member,
+                        data,
+                        Self::writable_mask(),
+                        Self::clear_mask(),
+                    );
+                }
+
+                #visibility const fn byte(&self, index: usize) -> u8 {
+                    ::core::assert!(index < #data_type_size);
+                    (self.#member >> (8 * index)) as u8
+                }
+
+                #visibility const fn set_byte(&mut self, index: usize, byte: u8) {
+                    ::core::assert!(index < #data_type_size);
+                    let mask: #data_type = 0xFF << (8 * index);
+                    self.#member = #helpers::write(
+                        self.#member,
+                        (byte as #data_type) << (8 * index),
+                        Self::writable_mask() & mask,
+                        Self::clear_mask() & mask,
+                    );
+                }
+            }
+        }
+    };
+
+    let size = bitfield.size();
+    let checked = quote! {
+        /// Returns the byte at `index`, or `None` if it is out of bounds.
+        #visibility const fn checked_byte(&self, index: usize) -> ::core::option::Option<u8> {
+            if index < #size {
+                ::core::option::Option::Some(self.byte(index))
+            } else {
+                ::core::option::Option::None
+            }
+        }
+
+        /// Sets the byte at `index` like `set_byte`, or fails if it is out of
+        /// bounds.
+        #visibility const fn checked_set_byte(
+            &mut self,
+            index: usize,
+            byte: u8,
+        ) -> ::core::result::Result<(), ::bitfield_runtime::OutOfBounds> {
+            if index < #size {
+                self.set_byte(index, byte);
+                ::core::result::Result::Ok(())
+            } else {
+                ::core::result::Result::Err(::bitfield_runtime::OutOfBounds)
+            }
+        }
+    };
+
+    let storage_type = match &bitfield.tuple {
+        Some(tuple) => quote! { (#tuple #data_type); },
+        None => quote! { { data: #data_type } },
+    };
+
+    Ok(quote! {
+        #(#attributes)*
+        #visibility struct #ident #storage_type
+
+        #debug
+
+        impl #ident {
+            #(#constants)*
+
+            #layout
+
+            #visibility const fn data_mask() -> #data_type {
+                #data_mask
+            }
+
+            /// Bits which `set_data` and `set_byte` may change.
+            #visibility const fn writable_mask() -> #data_type {
+                #writable_mask
+            }
+
+            /// Write-one-to-clear bits.
+            #visibility const fn clear_mask() -> #data_type {
+                #clear_mask
+            }
+
+            #storage
+
+            #checked
+
+            #bytes
+
+            #diff
+
+            #(#functions)*
+        }
+
+        #(#checks)*
+
+        #builder
+
+        #mmio
+
+        #field_value
+
+        #bitfield_trait
+
+        #operators
+
+        #serde
+
+        impl ::core::convert::From<#data_type> for #ident {
+            fn from(value: #data_type) -> Self {
+                #ident::new(value)
+            }
+        }
+
+        impl ::core::convert::From<#ident> for #data_type {
+            fn from(value: #ident) -> Self {
+                value.data()
+            }
+        }
+    })
+}
+
+/// Changed fields between two values, entry by entry for array fields.
+fn expand_diff(bitfield: &Bitfield) -> TokenStream {
+    let member = bitfield.member();
+    let visibility = &bitfield.visibility;
+    let helpers = bitfield.helpers();
+
+    let mut entries = vec![];
+    for field in bitfield.fields.iter() {
+        let name = field.ident.unraw().to_string();
+        let shift = bitfield.entry_shift(field);
+        let len = bitfield.width(field);
+        let signed = matches!(Primitive::of(&field.ty), Some(Primitive::Signed(_)));
+        match field.count {
+            Some(count) => entries.extend((0..count).map(|index| {
+                quote! {
+                    (#name, ::core::option::Option::Some(#index), {
+                        let index: usize = #index;
+                        #shift
+                    }, #len, #signed)
+                }
+            })),
+            None => entries.push(quote! {
+                (#name, ::core::option::Option::None, #shift, #len, #signed)
+            }),
+        }
+    }
+
+    let count = entries.len();
+    let get = match bitfield.bytes() {
+        Some(_) => {
+            let big_endian = bitfield.options.big_endian.is_some();
+            quote! { #helpers::get(&value.#member, shift, len, #big_endian) }
+        }
+        None => quote! { #helpers::get(value.#member, shift, len) as u128 },
+    };
+
+    quote! {
+        /// Fields whose bits differ from `other`, in declaration order.
+        #visibility fn diff<'a>(
+            &'a self,
+            other: &'a Self,
+        ) -> impl ::core::iter::Iterator<Item = ::bitfield_runtime::FieldChange> + 'a {
+            let entries: [(&'static str, ::core::option::Option<usize>, usize, usize, bool); #count] =
+                [#(#entries),*];
+            entries.into_iter().filter_map(move |(name, index, shift, len, signed)| {
+                let get = |value: &Self| {
+                    let bits: u128 = #get;
+                    if signed {
+                        let extend = 128 - len;
+                        ::bitfield_runtime::ChangeValue::Signed(((bits << extend) as i128) >> extend)
+                    } else {
+                        ::bitfield_runtime::ChangeValue::Unsigned(bits)
+                    }
+                };
+                let (old, new) = (get(self), get(other));
+                (old != new).then_some(::bitfield_runtime::FieldChange { name, index, old, new })
+            })
+        }
+    }
+}
+
+/// Conversions from and to bytes in either byte order, and from and to
+/// buffers in the bitfield's byte order.
+fn expand_bytes(bitfield: &Bitfield) -> TokenStream {
+    let member = bitfield.member();
+    let visibility = &bitfield.visibility;
+    let data_type = &bitfield.ty;
+
+    let (size, conversions) = match bitfield.bytes() {
+        Some(bytes) => {
+            let (stored, reversed) = if bitfield.options.big_endian.is_some() {
+                (quote! { be }, quote! { le })
+            } else {
+                (quote! { le }, quote! { be })
+            };
+            let to_stored = format_ident!("to_{stored}_bytes");
+            let to_reversed = format_ident!("to_{reversed}_bytes");
+            let from_stored = format_ident!("from_{stored}_bytes");
+            let from_reversed = format_ident!("from_{reversed}_bytes");
+            (
+                quote! { #bytes },
+                quote! {
+                    #visibility const fn #to_stored(&self) -> [u8; #bytes] {
+                        self.#member
+                    }
+
+                    #visibility const fn #to_reversed(&self) -> [u8; #bytes] {
+                        ::bitfield_runtime::__private::bytes::reverse(self.#member)
+                    }
+
+                    #visibility const fn #from_stored(bytes: [u8; #bytes]) -> Self {
+                        Self::new(bytes)
+                    }
+
+                    #visibility const fn #from_reversed(bytes: [u8; #bytes]) -> Self {
+                        Self::new(::bitfield_runtime::__private::bytes::reverse(bytes))
+                    }
+                },
+            )
+        }
+        None => {
+            let size = quote! { ::core::mem::size_of::<#data_type>() };
+            (
+                size.clone(),
+                quote! {
+                    #visibility const fn to_le_bytes(&self) -> [u8; #size] {
+                        self.#member.to_le_bytes()
+                    }
+
+                    #visibility const fn to_be_bytes(&self) -> [u8; #size] {
+                        self.#member.to_be_bytes()
+                    }
+
+                    #visibility const fn from_le_bytes(bytes: [u8; #size]) -> Self {
+                        Self::new(#data_type::from_le_bytes(bytes))
+                    }
+
+                    #visibility const fn from_be_bytes(bytes: [u8; #size]) -> Self {
+                        Self::new(#data_type::from_be_bytes(bytes))
+                    }
+                },
+            )
+        }
+    };
+
+    let (to_bytes, from_bytes) = if bitfield.options.big_endian.is_some() {
+        (quote! { to_be_bytes }, quote! { from_be_bytes })
+    } else {
+        (quote! { to_le_bytes }, quote! { from_le_bytes })
+    };
+
+    quote! {
+        #conversions
+
+        /// Reads the bitfield from the start of `bytes`, clearing the bits
+        /// outside of the fields.
+        #visibility const fn read_from(bytes: &[u8]) -> Self {
+            ::core::assert!(bytes.len() >= #size);
+            let mut data = [0; #size];
+            let mut index = 0;
+            while index < #size {
+                data[index] = bytes[index];
+                index += 1;
+            }
+            Self::#from_bytes(data)
+        }
+
+        /// Writes the fields to the start of `bytes`, keeping the bits outside
+        /// of the fields.
+        #visibility const fn write_to(&self, bytes: &mut [u8]) {
+            ::core::assert!(bytes.len() >= #size);
+            let data = self.#to_bytes();
+            let mask = Self { #member: Self::data_mask() }.#to_bytes();
+            let mut index = 0;
+            while index < #size {
+                bytes[index] = (bytes[index] & !mask[index]) | data[index];
+                index += 1;
+            }
+        }
+
+        /// Reads the bitfield like `read_from`, or returns `None` if `bytes`
+        /// is too short.
+        #visibility const fn try_read_from(bytes: &[u8]) -> ::core::option::Option<Self> {
+            if bytes.len() >= #size {
+                ::core::option::Option::Some(Self::read_from(bytes))
+            } else {
+                ::core::option::Option::None
+            }
+        }
+
+        /// Writes the fields like `write_to`, or fails if `bytes` is too
+        /// short.
+        #visibility const fn try_write_to(
+            &self,
+            bytes: &mut [u8],
+        ) -> ::core::result::Result<(), ::bitfield_runtime::OutOfBounds> {
+            if bytes.len() >= #size {
+                self.write_to(bytes);
+                ::core::result::Result::Ok(())
+            } else {
+                ::core::result::Result::Err(::bitfield_runtime::OutOfBounds)
+            }
+        }
+    }
+}
+
+fn expand_operators(bitfield: &Bitfield) -> TokenStream {
+    let member = bitfield.member();
+    let ident = &bitfield.ident;
+    let binary = [
+        (quote! { BitAnd }, quote! { bitand }, quote! { &= }),
+        (quote! { BitOr }, quote! { bitor }, quote! { |= }),
+        (quote! { BitXor }, quote! { bitxor }, quote! { ^= }),
+    ];
+
+    let binary = binary.iter().map(|(name, function, operator)| {
+        let body = match bitfield.bytes() {
+            Some(_) => quote! {
+                let mut data = self.#member;
+                for (data, rhs) in data.iter_mut().zip(rhs.#member) {
+                    *data #operator rhs;
+                }
+            },
+            None => quote! {
+                let mut data = self.#member;
+                data #operator rhs.#member;
+            },
+        };
+        quote! {
+            impl ::core::ops::#name for #ident {
+                type Output = Self;
+
+                fn #function(self, rhs: Self) -> Self {
+                    #body
+                    Self::new(data)
+                }
+            }
+        }
+    });
+
+    let not = match bitfield.bytes() {
+        Some(_) => quote! { self.#member.map(|data| !data) },
+        None => quote! { !self.#member },
+    };
+
+    quote! {
+        #(#binary)*
+
+        impl ::core::ops::Not for #ident {
+            type Output = Self;
+
+            fn not(self) -> Self {
+                Self::new(#not)
+            }
+        }
+    }
+}
+
+fn expand_field_value(bitfield: &Bitfield) -> Option<TokenStream> {
+    let member = bitfield.member();
+    let ident = &bitfield.ident;
+    let data_type = &bitfield.ty;
+    let bits = bitfield.bits();
+    let target_bits = bitfield.target_bits();
+    let helpers = bitfield.helpers();
+    let big_endian = bitfield.options.big_endian.is_some();
+
+    let (from_bits, into_bits) = match bitfield.bytes() {
+        // Wider byte arrays cannot be nested
+        Some(_) if bits > 128 => return None,
+        Some(_) => (
+            quote! {
+                let mut data = [0; #bits / 8];
+                #helpers::set(&mut data, 0, #bits, bits, #big_endian);
+            },
+            quote! { #helpers::get(&self.#member, 0, #bits, #big_endian) },
+        ),
+        None => (
+            quote! {
+                let data = bits as #data_type;
+            },
+            quote! { self.#member as u128 },
+        ),
+    };
+
+    Some(quote! {
+        impl ::bitfield_runtime::FieldValue for #ident {
+            const BITS: usize = #target_bits;
+            const EXHAUSTIVE: bool = true;
+
+            fn from_bits(bits: u128) -> ::core::option::Option<Self> {
+                #from_bits
+                ::core::option::Option::Some(Self::new(data))
+            }
+
+            fn into_bits(self) -> u128 {
+                #into_bits
+            }
+        }
+    })
+}
+
+fn expand_bitfield_trait(bitfield: &Bitfield) -> TokenStream {
+    let member = bitfield.member();
+    let ident = &bitfield.ident;
+    let data_type = &bitfield.ty;
+    let fields = bitfield.fields.iter().map(Field::info);
+
+    quote! {
+        impl ::bitfield_runtime::Bitfield for #ident {
+            type Raw = #data_type;
+
+            const MASK: #data_type = #ident::data_mask();
+
+            const FIELDS: &'static [::bitfield_runtime::FieldInfo] = {
+                use ::bitfield_runtime::{Access as __Access, FieldInfo as __FieldInfo};
+                &[#(#fields),*]
+            };
+
+            fn raw(&self) -> #data_type {
+                self.#member
+            }
+
+            fn from_raw(raw: #data_type) -> Self {
+                Self::new(raw)
+            }
+        }
+    }
+}
+
+/// Statements reading a field into `value` and writing `value` to it, split
+/// around the conversion from and to the raw bits.
+struct Accessor<'a> {
+    field: &'a Field,
+    get: TokenStream,
+    cast: TokenStream,
+    into_raw: TokenStream,
+    set: TokenStream,
+}
+
+/// Implements `Serialize` and `Deserialize` as the raw data or as a map of
+/// fields, which rejects values that do not fit into their range.
+fn expand_serde(bitfield: &Bitfield, serde: Serde, accessors: &[Accessor]) -> TokenStream {
+    let member = bitfield.member();
+    let ident = &bitfield.ident;
+    let data_type = &bitfield.ty;
+
+    if serde == Serde::Raw {
+        return quote! {
+            impl ::serde::Serialize for #ident {
+                fn serialize<S>(&self, serializer: S) -> ::core::result::Result<S::Ok, S::Error>
+                where
+                    S: ::serde::Serializer,
+                {
+                    ::serde::Serialize::serialize(&self.#member, serializer)
+                }
+            }
+
+            impl<'de> ::serde::Deserialize<'de> for #ident {
+                fn deserialize<D>(deserializer: D) -> ::core::result::Result<Self, D::Error>
+                where
+                    D: ::serde::Deserializer<'de>,
+                {
+                    let data = <#data_type as ::serde::Deserialize>::deserialize(deserializer)?;
+                    let value = Self::new(data);
+                    if value.#member != data {
+                        return ::core::result::Result::Err(::serde::de::Error::custom("bits outside of the fields are set"));
+                    }
+                    ::core::result::Result::Ok(value)
+                }
+            }
+        };
+    }
+
+    let raw_type = bitfield.raw_type();
+    let mut fields = vec![];
+    let mut reads = vec![];
+    let mut writes = vec![];
+    let mut checks = vec![];
+    for accessor in accessors {
+        let Accessor {
+            field,
+            get,
+            cast,
+            into_raw,
+            set,
+        } = accessor;
+        let ident = &field.ident;
+        let field_type = &field.ty;
+        let width = bitfield.width(field);
+        let error = format!("value of `{}` does not fit into its range", ident.unraw());
+
+        // Ranges of constant expressions may cover the whole type
+        let narrow = |bits| {
+            field
+                .range
+                .known
+                .as_ref()
+                .is_none_or(|range| range.len() / field.count.unwrap_or(1) < bits)
+        };
+
+        let validate = match Primitive::of(field_type) {
+            Some(Primitive::Unsigned(bits)) if narrow(bits) => {
+                quote! {
+                    if value.checked_shr(#width as u32).unwrap_or(0) != 0 {
+                        return ::core::result::Result::Err(#error);
+                    }
+                }
+            }
+            Some(Primitive::Signed(bits)) if narrow(bits) => {
+                let extend = quote! { (<#field_type>::BITS as usize - #width) };
+                quote! {
+                    if (value << #extend) >> #extend != value {
+                        return ::core::result::Result::Err(#error);
+                    }
+                }
+            }
+            _ => quote! {},
+        };
+
+        // Patterns without a value are kept as their raw bits to round-trip
+        let (field_type, read, write) = match option_type(field_type) {
+            Some(value_type) => {
+                checks.push(quote_spanned! { field_type.span() =>
+                    const _: () = ::core::assert!(
+                        #width <= 64,
+                        "Bitfield serde map needs Option fields of at most 64 bits",
+                    );
+                });
+                (
+                    quote! { Entry<#value_type> },
+                    quote! {
+                        #get
+                        #cast
+                        let value = match value {
+                            ::core::option::Option::Some(value) => Entry::Value(value),
+                            ::core::option::Option::None => {
+                                #get
+                                Entry::Bits(value as u64)
+                            }
+                        };
+                    },
+                    quote! {
+                        match value {
+                            Entry::Value(value) => {
+                                #into_raw
+                                #set
+                            }
+                            Entry::Bits(bits) => {
+                                if bits.checked_shr(#width as u32).unwrap_or(0) != 0 {
+                                    return ::core::result::Result::Err(#error);
+                                }
+                                let value = bits as #raw_type;
+                                #set
+                            }
+                        }
+                    },
+                )
+            }
+            None => (
+                field_type.to_token_stream(),
+                quote! {
+                    #get
+                    #cast
+                },
+                quote! {
+                    #validate
+                    #into_raw
+                    #set
+                },
+            ),
+        };
+
+        match field.count {
+            Some(count) => {
+                let indices = 0..count;
+                fields.push(quote! { #ident: [#field_type; #count] });
+                reads.push(quote! {
+                    #ident: [#({
+                        let index = #indices;
+                        #read
+                        value
+                    }),*]
+                });
+                writes.push(quote! {
+                    for (index, value) in map.#ident.into_iter().enumerate() {
+                        #write
+                    }
+                });
+            }
+            None => {
+                fields.push(quote! { #ident: #field_type });
+                reads.push(quote! {
+                    #ident: {
+                        #read
+                        value
+                    }
+                });
+                writes.push(quote! {
+                    let value = map.#ident;
+                    #write
+                });
+            }
+        }
+    }
+
+    let name = ident.unraw().to_string();
+    let zero = bitfield.zero();
+    let entry = (!checks.is_empty()).then(|| {
+        quote! {
+            #[derive(::serde::Serialize, ::serde::Deserialize)]
+            #[serde(untagged)]
+            enum Entry<T> {
+                Value(T),
+                Bits(u64),
+            }
+        }
+    });
+
+    quote! {
+        const _: () = {
+            #entry
+
+            #(#checks)*
+
+            #[derive(::serde::Serialize, ::serde::Deserialize)]
+            #[serde(rename = #name, deny_unknown_fields)]
+            struct Map {
+                #(#fields,)*
+            }
+
+            impl #ident {
+                fn __to_map(&self) -> Map {
+                    Map {
+                        #(#reads,)*
+                    }
+                }
+
+                fn __set_map(&mut self, map: Map) -> ::core::result::Result<(), &'static str> {
+                    #(#writes)*
+                    ::core::result::Result::Ok(())
+                }
+            }
+
+            impl ::serde::Serialize for #ident {
+                fn serialize<S>(&self, serializer: S) -> ::core::result::Result<S::Ok, S::Error>
+                where
+                    S: ::serde::Serializer,
+                {
+                    ::serde::Serialize::serialize(&self.__to_map(), serializer)
+                }
+            }
+
+            impl<'de> ::serde::Deserialize<'de> for #ident {
+                fn deserialize<D>(deserializer: D) -> ::core::result::Result<Self, D::Error>
+                where
+                    D: ::serde::Deserializer<'de>,
+                {
+                    let map = <Map as ::serde::Deserialize>::deserialize(deserializer)?;
+                    let mut value = Self::new(#zero);
+                    value.__set_map(map).map_err(::serde::de::Error::custom)?;
+                    ::core::result::Result::Ok(value)
+                }
+            }
+        };
+    }
+}
+
+fn expand_debug(bitfield: &Bitfield) -> TokenStream {
+    let ident = &bitfield.ident;
+    let fields = bitfield
+        .fields
+        .iter()
+        .filter(|field| field.access.readable());
+    let fields = fields.map(|field| {
+        let ident = &field.ident;
+        let name = ident.unraw().to_string();
+        let unsigned = matches!(Primitive::of(&field.ty), Some(Primitive::Unsigned(_)));
+        if let Some(count) = field.count {
+            let indices = 0..count;
+            if unsigned && field.pipe.is_none() {
+                quote! {
+                    .field(#name, &[#(::core::format_args!("{:#x}", self.#ident(#indices))),*])
+                }
+            } else {
+                quote! { .field(#name, &[#(self.#ident(#indices)),*]) }
+            }
+        } else if unsigned && field.pipe.is_none() {
+            quote! { .field(#name, &::core::format_args!("{:#x}", self.#ident())) }
+        } else {
+            quote! { .field(#name, &self.#ident()) }
+        }
+    });
+    let name = ident.unraw().to_string();
+
+    quote! {
+        impl ::core::fmt::Debug for #ident {
+            #[allow(deprecated)]
+            fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                f.debug_struct(#name)
+                    #(#fields)*
+                    .finish()
+            }
+        }
+    }
+}
+
+/// Generates the `LAYOUT` constant of `#[bitfield(describe)]`.
+fn expand_layout(bitfield: &Bitfield) -> TokenStream {
+    let visibility = &bitfield.visibility;
+    let name = bitfield.ident.unraw().to_string();
+    let bits = bitfield.target_bits();
+    let big_endian = bitfield.options.big_endian.is_some();
+    let doc = doc_string(&bitfield.attributes);
+    let fields = bitfield.fields.iter().map(|field| {
+        let info = field.info();
+        let doc = doc_string(&field.attributes);
+        quote! {
+            ::bitfield_runtime::FieldLayout {
+                info: #info,
+                doc: #doc,
+            }
+        }
+    });
+
+    quote! {
+        #visibility const LAYOUT: ::bitfield_runtime::Layout = ::bitfield_runtime::Layout {
+            name: #name,
+            bits: #bits,
+            big_endian: #big_endian,
+            doc: #doc,
+            fields: {
+                use ::bitfield_runtime::{Access as __Access, FieldInfo as __FieldInfo};
+                &[#(#fields),*]
+            },
+        };
+    }
+}
+
+fn expand_mmio(bitfield: &Bitfield) -> TokenStream {
+    let member = bitfield.member();
+    let visibility = &bitfield.visibility;
+    let ident = &bitfield.ident;
+    let data_type = &bitfield.ty;
+    let register = format_ident!("{}Register", ident.unraw());
+
+    let keep = match bitfield.bytes() {
+        Some(bytes) => quote! {
+            let mut data = value.#member;
+            let mut index = 0;
+            while index < #bytes {
+                data[index] &= !#ident::clear_mask()[index];
+                index += 1;
+            }
+        },
+        None => quote! {
+            let data = value.#member & !#ident::clear_mask();
+        },
+    };
+
+    quote! {
+        /// Volatile register holding a bitfield, for memory-mapped I/O.
+        #[repr(transparent)]
+        #visibility struct #register {
+            data: ::core::cell::UnsafeCell<#data_type>,
+        }
+
+        impl #register {
+            /// Creates a register at `pointer`, which may point to device
+            /// memory or to plain memory.
+            ///
+            /// # Safety
+            ///
+            /// `pointer` must be aligned and valid for volatile reads and
+            /// writes for `'a`.
+            #visibility unsafe fn from_ptr<'a>(pointer: *mut #data_type) -> &'a Self {
+                unsafe { &*(pointer as *const Self) }
+            }
+
+            #visibility const fn as_ptr(&self) -> *mut #data_type {
+                self.data.get()
+            }
+
+            #visibility fn read(&self) -> #ident {
+                #ident::new(unsafe { self.data.get().read_volatile() })
+            }
+
+            #visibility fn write(&self, value: #ident) {
+                unsafe { self.data.get().write_volatile(value.#member) }
+            }
+
+            /// Reads, modifies and writes back the register. Write-one-to-clear
+            /// bits are written as zero to keep them set.
+            #visibility fn modify(&self, f: impl ::core::ops::FnOnce(#ident) -> #ident) {
+                let value = f(self.read());
+                #keep
+                unsafe { self.data.get().write_volatile(data) }
+            }
+        }
+    }
+}
+
+fn expand_builder(bitfield: &Bitfield, methods: Vec<(&Field, Option<TokenStream>)>) -> TokenStream {
+    let visibility = &bitfield.visibility;
+    let ident = &bitfield.ident;
+    let builder = format_ident!("{}Builder", ident.unraw());
+
+    // Overlapping fields are alternative views and are not tracked
+    let tracked: Vec<_> = methods
+        .iter()
+        .filter(|(field, _)| matches!(field.overlap, Overlap::None))
+        .map(|(field, _)| &field.ident)
+        .collect();
+    let states: Vec<_> = (0..tracked.len())
+        .map(|index| format_ident!("S{index}"))
+        .collect();
+
+    let methods = methods.into_iter().map(|(field, constness)| {
+        let attributes = &field.attributes;
+        let allow_deprecated = field.allow_deprecated();
+        let visibility = &field.visibility;
+        let ident = &field.ident;
+        let ident_with = format_ident!("with_{}", ident.unraw());
+        let value_type = field.input_type();
+        let output = match tracked.iter().position(|ident| **ident == field.ident) {
+            Some(index) => {
+                let mut states = states.clone();
+                states[index] = Ident::new("true", proc_macro2::Span::call_site());
+                quote! { #builder<#(#states),*> }
+            }
+            None => quote! { Self },
+        };
+        match field.count {
+            Some(count) => quote! {
+                #(#attributes)*
+                #allow_deprecated
+                #visibility #constness fn #ident(self, values: [#value_type; #count]) -> #output {
+                    let mut value = self.value;
+                    let mut index = 0;
+                    while index < #count {
+                        value = value.#ident_with(index, values[index]);
+                        index += 1;
+                    }
+                    #builder { value }
+                }
+            },
+            None => quote! {
+                #(#attributes)*
+                #allow_deprecated
+                #visibility #constness fn #ident(self, value: #value_type) -> #output {
+                    #builder { value: self.value.#ident_with(value) }
+                }
+            },
+        }
+    });
+
+    let zero = bitfield.zero();
+    let initial = tracked.iter().map(|_| quote! { false });
+    let complete = tracked.iter().map(|_| quote! { true });
+
+    quote! {
+        /// Builder which tracks the assigned fields in its type.
+        #visibility struct #builder<#(const #states: bool),*> {
+            value: #ident,
+        }
+
+        impl #ident {
+            #visibility const fn builder() -> #builder<#(#initial),*> {
+                #builder { value: #ident::new(#zero) }
+            }
+        }
+
+        impl<#(const #states: bool),*> #builder<#(#states),*> {
+            #(#methods)*
+
+            /// Builds the value, leaving unassigned fields zeroed.
+            #visibility const fn build(self) -> #ident {
+                self.value
+            }
+        }
+
+        impl #builder<#(#complete),*> {
+            /// Builds the value and fails to compile unless every field was
+            /// assigned.
+            #visibility const fn build_complete(self) -> #ident {
+                self.value
+            }
+        }
+    }
+}
+
+/// Checks the layout of known ranges and returns const assertions for the
+/// others.
+fn check_layout(bitfield: &Bitfield) -> Result<Vec<TokenStream>> {
+    let mut checks = vec![];
+    let mut errors: Option<Error> = None;
+    let mut push = |error: Error| match &mut errors {
+        Some(errors) => errors.combine(error),
+        None => errors = Some(error),
+    };
+
+    let fields: Vec<_> = bitfield.fields.iter().collect();
+    for (index, field) in fields.iter().enumerate() {
+        for other in &fields[..index] {
+            if field.may_overlap(other) {
+                continue;
+            }
+            let message = || format!("Bitfield range overlaps field `{}`", other.ident);
+            match (&field.range.known, &other.range.known) {
+                (Some(range), Some(other)) => {
+                    if range.start < other.end && other.start < range.end {
+                        push(Error::new_spanned(&field.range, message()));
+                    }
+                }
+                _ => {
+                    let message = message();
+                    let (shift, bits) = (
+                        bitfield.constant(field, "SHIFT"),
+                        bitfield.constant(field, "BITS"),
+                    );
+                    let (other_shift, other_bits) = (
+                        bitfield.constant(other, "SHIFT"),
+                        bitfield.constant(other, "BITS"),
+                    );
+                    checks.push(quote_spanned! { field.range.span() =>
+                        const _: () = ::core::assert!(
+                            !(#shift < #other_shift + #other_bits && #other_shift < #shift + #bits),
+                            #message,
+                        );
+                    });
+                }
+            }
+        }
+    }
+
+    // The width of a `usize` is only known on the target
+    let known: Option<Vec<_>> = match Primitive::is_pointer_sized(&bitfield.ty) {
+        true => None,
+        false => fields
+            .iter()
+            .map(|field| field.range.known.clone())
+            .collect(),
+    };
+    match (bitfield.options.exhaustive, known) {
+        (Some(span), Some(known)) => {
+            let mut assigned = vec![false; bitfield.bits()];
+            for range in known {
+                assigned[range].fill(true);
+            }
+
+            let mut start = 0;
+            while let Some(offset) = assigned[start..].iter().position(|assigned| !assigned) {
+                let begin = start + offset;
+                let end = assigned[begin..]
+                    .iter()
+                    .position(|assigned| *assigned)
+                    .map_or(assigned.len(), |offset| begin + offset);
+                push(Error::new(
+                    span,
+                    format!("Bitfield bits {begin}..{end} are not assigned to a field"),
+                ));
+                start = end;
+            }
+        }
+        (Some(span), None) => {
+            let ident = &bitfield.ident;
+            let assigned = match bitfield.bytes() {
+                Some(bytes) => quote! {{
+                    let mask = #ident::data_mask();
+                    let mut assigned = true;
+                    let mut index = 0;
+                    while index < #bytes {
+                        assigned &= mask[index] == 0xFF;
+                        index += 1;
+                    }
+                    assigned
+                }},
+                None => {
+                    let data_type = &bitfield.ty;
+                    quote! { #ident::data_mask() == #data_type::MAX }
+                }
+            };
+            checks.push(quote_spanned! { span =>
+                const _: () = ::core::assert!(#assigned, "Bitfield bits are not assigned to a field");
+            });
+        }
+        _ => {}
+    }
+
+    errors.map_or(Ok(checks), Err)
+}
+
+pub fn make_field_value(input: TokenStream) -> Result<TokenStream> {
+    let input = parse2::<DeriveInput>(input)?;
+    let data = match &input.data {
+        Data::Enum(data) => data,
+        _ => {
+            return Err(Error::new(
+                input.ident.span(),
+                "FieldValue can only be derived for enums",
+            ))
+        }
+    };
+
+    let mut variants = vec![];
+    let mut next = Some(0u64);
+    for variant in data.variants.iter() {
+        if !matches!(variant.fields, Fields::Unit) {
+            return Err(Error::new_spanned(
+                &variant.fields,
+                "FieldValue variants must not have fields",
+            ));
+        }
+        let discriminant = match &variant.discriminant {
+            Some((_, expr)) => parse_int(expr)?,
+            None => next.ok_or_else(|| {
+                Error::new(variant.ident.span(), "FieldValue discriminant overflows")
+            })?,
+        };
+        variants.push((&variant.ident, discriminant));
+        next = discriminant.checked_add(1);
+    }
+
+    let max = variants.iter().map(|(_, value)| *value).max().unwrap_or(0);
+    let required = (u64::BITS - max.leading_zeros()).max(1) as usize;
+    let mut bits = required;
+    for attribute in input.attrs.iter() {
+        if attribute.path().is_ident("bits") {
+            let literal = attribute.parse_args::<LitInt>()?;
+            bits = literal.base10_parse()?;
+            if !(required..=64).contains(&bits) {
+                return Err(Error::new_spanned(
+                    &literal,
+                    format!("FieldValue needs between {required} and 64 bits"),
+                ));
+            }
+        }
+    }
+
+    let mut discriminants: Vec<_> = variants.iter().map(|(_, value)| *value).collect();
+    discriminants.sort_unstable();
+    discriminants.dedup();
+    let exhaustive = bits < 64 && discriminants.len() as u64 == 1 << bits;
+
+    let ident = &input.ident;
+    let arms = variants.iter().map(|(variant, value)| {
+        let value = proc_macro2::Literal::u64_unsuffixed(*value);
+        quote! {
+            #value => ::core::option::Option::Some(#ident::#variant),
+        }
+    });
+
+    Ok(quote! {
+        impl ::bitfield_runtime::FieldValue for #ident {
+            const BITS: usize = #bits;
+            const EXHAUSTIVE: bool = #exhaustive;
+
+            fn from_bits(bits: u128) -> ::core::option::Option<Self> {
+                match bits {
+                    #(#arms)*
+                    _ => ::core::option::Option::None,
+                }
+            }
+
+            fn into_bits(self) -> u128 {
+                self as u128
+            }
+        }
+    })
+}
+
+fn option_type(ty: &Type) -> Option<&Type> {
+    if let Type::Path(path) = ty {
+        let segment = path.path.segments.last()?;
+        if segment.ident == "Option" {
+            if let PathArguments::AngleBracketed(arguments) = &segment.arguments {
+                if let Some(GenericArgument::Type(ty)) = arguments.args.first() {
+                    return Some(ty);
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Primitive type of a field or of the backing data, matched structurally.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Primitive {
+    Bool,
+    Unsigned(usize),
+    Signed(usize),
+}
+
+impl Primitive {
+    pub fn of(ty: &Type) -> Option<Self> {
+        let Type::Path(TypePath { qself: None, path }) = ty else {
+            return None;
+        };
+        let ident = path.get_ident()?;
+        let primitive = match ident.to_string().as_str() {
+            "bool" => Primitive::Bool,
+            "u8" => Primitive::Unsigned(8),
+            "u16" => Primitive::Unsigned(16),
+            "u32" => Primitive::Unsigned(32),
+            "u64" => Primitive::Unsigned(64),
+            // Widest target, the width of the actual target is asserted
+            "usize" => Primitive::Unsigned(64),
+            "u128" => Primitive::Unsigned(128),
+            "i8" => Primitive::Signed(8),
+            "i16" => Primitive::Signed(16),
+            "i32" => Primitive::Signed(32),
+            "i64" => Primitive::Signed(64),
+            "isize" => Primitive::Signed(64),
+            "i128" => Primitive::Signed(128),
+            _ => return None,
+        };
+        Some(primitive)
+    }
+
+    /// Whether the width of `ty` depends on the target, unlike the width of
+    /// the primitive.
+    pub fn is_pointer_sized(ty: &Type) -> bool {
+        let Type::Path(TypePath { qself: None, path }) = ty else {
+            return false;
+        };
+        path.is_ident("usize") || path.is_ident("isize")
+    }
+
+    pub fn bits(self) -> usize {
+        match self {
+            Primitive::Bool => 1,
+            Primitive::Unsigned(bits) | Primitive::Signed(bits) => bits,
+        }
+    }
+}
+
+struct Bitfield {
+    pub attributes: Vec<Attribute>,
+    pub options: Options,
+    pub visibility: Visibility,
+    pub ident: Ident,
+    /// Visibility of the backing data of the tuple struct form.
+    pub tuple: Option<Visibility>,
+    pub ty: Type,
+    pub fields: Punctuated<Field, Token![,]>,
+}
+
+impl Bitfield {
+    pub fn bits(&self) -> usize {
+        match self.bytes() {
+            Some(bytes) => 8 * bytes,
+            // Checked by check_data_type
+            None => Primitive::of(&self.ty).map_or(0, Primitive::bits),
+        }
+    }
+
+    /// Width of the backing data on the target, which `bits` only bounds for
+    /// `usize`.
+    pub fn target_bits(&self) -> TokenStream {
+        match Primitive::is_pointer_sized(&self.ty) {
+            true => quote! { (<usize>::BITS as usize) },
+            false => self.bits().to_token_stream(),
+        }
+    }
+
+    /// Size of the backing data in bytes on the target.
+    pub fn size(&self) -> TokenStream {
+        match Primitive::is_pointer_sized(&self.ty) {
+            true => quote! { ::core::mem::size_of::<usize>() },
+            false => (self.bits() / 8).to_token_stream(),
+        }
+    }
+
+    /// Member holding the backing data.
+    pub fn member(&self) -> Member {
+        match self.tuple {
+            Some(_) => Member::Unnamed(Index::from(0)),
+            None => Member::Named(format_ident!("data")),
+        }
+    }
+
+    /// Length of the backing byte array, if any.
+    pub fn bytes(&self) -> Option<usize> {
+        match &self.ty {
+            Type::Array(array) => parse_int(&array.len).ok(),
+            _ => None,
+        }
+    }
+
+    /// Backing data with the bits of the matching fields set, combined from
+    /// their `_MASK` constants unless every range is known.
+    pub fn mask(&self, filter: impl Fn(&Field) -> bool) -> TokenStream {
+        let fields: Vec<_> = self.fields.iter().filter(|field| filter(field)).collect();
+        let known: Option<Vec<_>> = fields.iter().map(|field| self.known_bits(field)).collect();
+        if let Some(known) = known {
+            return self.mask_literal(known);
+        }
+        let masks: Vec<_> = fields
+            .iter()
+            .map(|field| self.constant(field, "MASK"))
+            .collect();
+        match self.bytes() {
+            Some(bytes) => quote! {
+                let mut mask = [0; #bytes];
+                let mut index = 0;
+                while index < #bytes {
+                    mask[index] = 0 #(| #masks[index])*;
+                    index += 1;
+                }
+                mask
+            },
+            None => quote! { 0 #(| #masks)* },
+        }
+    }
+
+    /// Literal backing data with the given bits set.
+    fn mask_literal(&self, ranges: Vec<Range<usize>>) -> TokenStream {
+        match self.bytes() {
+            Some(bytes) => {
+                let big_endian = self.options.big_endian.is_some();
+                let mut mask = vec![0u8; bytes];
+                for bit in ranges.into_iter().flatten() {
+                    let index = if big_endian {
+                        bytes - 1 - bit / 8
+                    } else {
+                        bit / 8
+                    };
+                    mask[index] |= 1 << (bit % 8);
+                }
+                quote! { [#(#mask),*] }
+            }
+            None => {
+                let mask = ranges.into_iter().fold(0, |mask, bits| {
+                    mask | (u128::MAX >> (128 - bits.len())) << bits.start
+                });
+                proc_macro2::Literal::u128_unsuffixed(mask).to_token_stream()
+            }
+        }
+    }
+
+    /// Expression of the field's `_MASK` constant.
+    pub fn field_mask(&self, field: &Field) -> TokenStream {
+        if let Some(bits) = self.known_bits(field) {
+            return self.mask_literal(vec![bits]);
+        }
+        let shift = self.constant(field, "SHIFT");
+        let bits = self.constant(field, "BITS");
+        match self.bytes() {
+            Some(bytes) => {
+                let helpers = self.helpers();
+                let big_endian = self.options.big_endian.is_some();
+                quote! {
+                    #helpers::mask::<#bytes>(#shift as usize, #bits as usize, #big_endian)
+                }
+            }
+            None => {
+                let ty = &self.ty;
+                quote! { ((u128::MAX >> (128 - #bits)) << #shift) as #ty }
+            }
+        }
+    }
+
+    /// Path of a per-field constant like `F1_MASK`.
+    pub fn constant(&self, field: &Field, suffix: &str) -> TokenStream {
+        let ident = &self.ident;
+        let name = field.ident.unraw().to_string().to_uppercase();
+        let constant = format_ident!("{name}_{suffix}");
+        quote! { #ident::#constant }
+    }
+
+    pub fn zero(&self) -> TokenStream {
+        match self.bytes() {
+            Some(bytes) => quote! { [0; #bytes] },
+            None => quote! { 0 },
+        }
+    }
+
+    /// Type used to move field values in and out of the backing data.
+    pub fn raw_type(&self) -> TokenStream {
+        match self.bytes() {
+            Some(_) => quote! { u128 },
+            None => self.ty.to_token_stream(),
+        }
+    }
+
+    /// Position of the field's least significant bit when the backing data
+    /// is read as one integer. Literal for known ranges.
+    pub fn shift(&self, field: &Field) -> TokenStream {
+        match field.range.known {
+            Some(_) => self.shift_value(field),
+            None => {
+                let shift = self.constant(field, "SHIFT");
+                quote! { (#shift as usize) }
+            }
+        }
+    }
+
+    /// Shift of the field, or of the array entry `index`.
+    pub fn entry_shift(&self, field: &Field) -> TokenStream {
+        let shift = self.shift(field);
+        let len = self.width(field);
+        match field.count {
+            Some(count) if self.options.big_endian.is_some() => {
+                quote! { (#shift + #len * (#count - 1 - index)) }
+            }
+            Some(_) => quote! { (#shift + #len * index) },
+            None => shift,
+        }
+    }
+
+    /// Shift of a field with a literal range, unless it counts from the most
+    /// significant bit of a `usize`.
+    pub fn known_shift(&self, field: &Field) -> Option<usize> {
+        let range = field.range.known.as_ref()?;
+        match self.options.big_endian {
+            Some(_) if Primitive::is_pointer_sized(&self.ty) => None,
+            Some(_) => Some(self.bits().saturating_sub(range.end)),
+            None => Some(range.start),
+        }
+    }
+
+    /// Bits of a field with a literal range, counted like its shift.
+    pub fn known_bits(&self, field: &Field) -> Option<Range<usize>> {
+        let shift = self.known_shift(field)?;
+        Some(shift..shift + field.range.known.as_ref()?.len())
+    }
+
+    /// Value of the field's `_SHIFT` constant.
+    pub fn shift_value(&self, field: &Field) -> TokenStream {
+        if let Some(shift) = self.known_shift(field) {
+            return proc_macro2::Literal::usize_unsuffixed(shift).to_token_stream();
+        }
+        let bits = self.target_bits();
+        match self.options.big_endian {
+            Some(_) => {
+                let end = &field.range.end;
+                quote! { usize::saturating_sub(#bits, #end) }
+            }
+            None => field.range.start.clone(),
+        }
+    }
+
+    /// Width of the field or of one array entry. Literal for known ranges.
+    pub fn width(&self, field: &Field) -> TokenStream {
+        let count = field.count.unwrap_or(1);
+        let bits = self.constant(field, "BITS");
+        match (&field.range.known, field.count) {
+            (Some(range), _) => {
+                proc_macro2::Literal::usize_unsuffixed(range.len() / count).to_token_stream()
+            }
+            (None, Some(count)) => quote! { (#bits as usize / #count) },
+            (None, None) => quote! { (#bits as usize) },
+        }
+    }
+
+    /// Runtime module with the accessor helpers for the backing data type.
+    pub fn helpers(&self) -> TokenStream {
+        match self.bytes() {
+            Some(_) => quote! { ::bitfield_runtime::__private::bytes },
+            None => {
+                let ty = &self.ty;
+                quote! { ::bitfield_runtime::__private::#ty }
+            }
+        }
+    }
+}
+
+impl Parse for Bitfield {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let mut options = Options::default();
+        let attributes = options.extract(input.call(Attribute::parse_outer)?)?;
+        let visibility = input.parse()?;
+        let _: Token![struct] = input.parse()?;
+        let ident = input.parse()?;
+        let (tuple, ty) = if input.peek(token::Paren) {
+            let content;
+            parenthesized!(content in input);
+            (Some(content.parse()?), content.parse()?)
+        } else {
+            let _: Token![:] = input.parse()?;
+            (None, input.parse()?)
+        };
+        check_data_type(&ty)?;
+
+        let content;
+        braced!(content in input);
+        let fields = content.parse_terminated(Field::parse, Token![,])?;
+
+        Ok(Bitfield {
+            attributes,
+            options,
+            visibility,
+            ident,
+            tuple,
+            ty,
+            fields,
+        })
+    }
+}
+
+/// Bitfields declared by one `bitfield!` invocation.
+struct Bitfields(Vec<Bitfield>);
+
+impl Parse for Bitfields {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let mut bitfields = vec![input.parse()?];
+        while !input.is_empty() {
+            bitfields.push(input.parse()?);
+        }
+        Ok(Bitfields(bitfields))
+    }
+}
+
+struct BitfieldArgs {
+    pub ty: Type,
+    pub options: Options,
+}
+
+impl Parse for BitfieldArgs {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let ty = input.parse()?;
+        let mut options = Options::default();
+        if input.parse::<Option<Token![,]>>()?.is_some() {
+            for meta in Punctuated::<Meta, Token![,]>::parse_terminated(input)? {
+                options.apply(&meta)?;
+            }
+        }
+        Ok(BitfieldArgs { ty, options })
+    }
+}
+
+/// Struct level options given as `#[bitfield(..)]`.
+#[derive(Default)]
+struct Options {
+    pub exhaustive: Option<proc_macro2::Span>,
+    /// Numbers bits from the most significant bit and stores byte arrays
+    /// big-endian.
+    pub big_endian: Option<proc_macro2::Span>,
+    /// Implements `Debug` with every field's decoded value.
+    pub debug: bool,
+    /// Generates a volatile register type holding the bitfield.
+    pub mmio: bool,
+    /// Generates the `LAYOUT` constant describing the fields.
+    pub describe: bool,
+    /// Generates a builder which tracks the assigned fields in its type.
+    pub builder: bool,
+    /// Generates `diff` listing the changed fields.
+    pub diff: bool,
+    pub serde: Option<Serde>,
+}
+
+/// Representation of a bitfield with the `serde` feature.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Serde {
+    Raw,
+    Map,
+}
+
+impl Options {
+    /// Applies `#[bitfield(..)]` attributes and returns the remaining ones.
+    pub fn extract(&mut self, attributes: Vec<Attribute>) -> Result<Vec<Attribute>> {
+        let mut remaining = vec![];
+        for attribute in attributes {
+            if !attribute.path().is_ident("bitfield") {
+                remaining.push(attribute);
+                continue;
+            }
+            let metas =
+                attribute.parse_args_with(Punctuated::<Meta, Token![,]>::parse_terminated)?;
+            for meta in metas.iter() {
+                self.apply(meta)?;
+            }
+        }
+        Ok(remaining)
+    }
+
+    pub fn apply(&mut self, meta: &Meta) -> Result<()> {
+        match meta {
+            Meta::Path(path) if path.is_ident("exhaustive") => {
+                self.exhaustive = Some(path.span());
+            }
+            Meta::Path(path) if path.is_ident("big_endian") => {
+                self.big_endian = Some(path.span());
+            }
+            Meta::Path(path) if path.is_ident("little_endian") => {
+                self.big_endian = None;
+            }
+            Meta::Path(path) if path.is_ident("debug") => {
+                self.debug = true;
+            }
+            Meta::Path(path) if path.is_ident("mmio") => {
+                self.mmio = true;
+            }
+            Meta::Path(path) if path.is_ident("describe") => {
+                self.describe = true;
+            }
+            Meta::Path(path) if path.is_ident("builder") => {
+                self.builder = true;
+            }
+            Meta::Path(path) if path.is_ident("diff") => {
+                self.diff = true;
+            }
+            Meta::Path(path) if path.is_ident("serde") => {
+                self.serde = Some(Serde::Raw);
+                check_serde(path)?;
+            }
+            Meta::NameValue(meta) if meta.path.is_ident("serde") => {
+                self.serde = match &meta.value {
+                    Expr::Lit(ExprLit {
+                        lit: Lit::Str(lit), ..
+                    }) if lit.value() == "raw" => Some(Serde::Raw),
+                    Expr::Lit(ExprLit {
+                        lit: Lit::Str(lit), ..
+                    }) if lit.value() == "map" => Some(Serde::Map),
+                    value => {
+                        return Err(Error::new_spanned(
+                            value,
+                            "Bitfield serde expected \"raw\" or \"map\"",
+                        ))
+                    }
+                };
+                check_serde(&meta.path)?;
+            }
+            _ => return Err(Error::new_spanned(meta, "Bitfield option is not supported")),
+        }
+        Ok(())
+    }
+}
+
+struct Field {
+    /// Number of entries of an array field, whose `ty` is the entry type.
+    pub count: Option<usize>,
+    pub overlap: Overlap,
+    pub access: Access,
+    pub visibility: Visibility,
+    pub ident: Ident,
+    pub ty: Type,
+    pub range: FieldRange,
+    pub pipe: Option<Pipe>,
+    /// Attributes forwarded to the accessors, like doc comments.
+    pub attributes: Vec<Attribute>,
+}
+
+/// How software may access a field, as given by `#[ro]`, `#[wo]` or `#[w1c]`.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Access {
+    ReadWrite,
+    ReadOnly,
+    WriteOnly,
+    WriteOneToClear,
+}
+
+impl Access {
+    pub fn readable(self) -> bool {
+        self != Access::WriteOnly
+    }
+
+    pub fn writable(self) -> bool {
+        matches!(self, Access::ReadWrite | Access::WriteOnly)
+    }
+}
+
+/// How a field may share bits with other fields.
+enum Overlap {
+    None,
+    Any,
+    Union(Ident),
+}
+
+impl Parse for Field {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let attributes = input.call(Attribute::parse_outer)?;
+        let visibility = input.parse()?;
+        let ident = input.parse()?;
+        let _: Token![:] = input.parse()?;
+        let ty: Type = input.parse()?;
+        let _: Token![@] = input.parse()?;
+        let range = input.parse()?;
+        let pipe = parse_pipe(input)?;
+        Field::new(attributes, visibility, ident, ty, range, pipe)
+    }
+}
+
+impl Field {
+    pub fn new(
+        attributes: Vec<Attribute>,
+        visibility: Visibility,
+        ident: Ident,
+        ty: Type,
+        range: FieldRange,
+        pipe: Option<Pipe>,
+    ) -> Result<Self> {
+        let (ty, count) = match ty {
+            Type::Array(array) => {
+                if Primitive::of(&array.elem).is_none() {
+                    return Err(Error::new_spanned(
+                        &array.elem,
+                        "Bitfield array field type must be an int or bool",
+                    ));
+                }
+                let count: usize = parse_int(&array.len)?;
+                if count == 0 {
+                    return Err(Error::new_spanned(
+                        &array.len,
+                        "Bitfield array field must not be empty",
+                    ));
+                }
+                (*array.elem, Some(count))
+            }
+            ty => (ty, None),
+        };
+
+        let mut overlap = Overlap::None;
+        let mut access = Access::ReadWrite;
+        let mut forwarded = vec![];
+        for attribute in attributes {
+            let is_flag =
+                |ident| matches!(&attribute.meta, Meta::Path(path) if path.is_ident(ident));
+            if is_flag("ro") {
+                access = Access::ReadOnly;
+            } else if is_flag("wo") {
+                access = Access::WriteOnly;
+            } else if is_flag("w1c") {
+                access = Access::WriteOneToClear;
+            } else if is_flag("overlap") {
+                overlap = Overlap::Any;
+            } else if attribute.path().is_ident("union") {
+                overlap = Overlap::Union(attribute.parse_args()?);
+            } else if attribute.path().is_ident("cfg") || attribute.path().is_ident("cfg_attr") {
+                // Masks, layout checks and the builder cover every field
+                return Err(Error::new_spanned(
+                    attribute,
+                    "Bitfield fields do not support conditional compilation",
+                ));
+            } else {
+                forwarded.push(attribute);
+            }
+        }
+
+        if !matches!(ty, Type::Path(_)) {
+            return Err(Error::new_spanned(
+                &ty,
+                "Bitfield field type must be an int, bool or FieldValue",
+            ));
+        }
+
+        Ok(Field {
+            count,
+            overlap,
+            access,
+            visibility,
+            ident,
+            ty,
+            range,
+            pipe,
+            attributes: forwarded,
+        })
+    }
+
+    /// Lint allowance for methods calling the accessors of a deprecated field.
+    pub fn allow_deprecated(&self) -> Option<TokenStream> {
+        self.attributes
+            .iter()
+            .any(|attribute| attribute.path().is_ident("deprecated"))
+            .then(|| quote! { #[allow(deprecated)] })
+    }
+
+    /// Expression of the field's `bitfield_runtime::FieldInfo`, which needs
+    /// `FieldInfo` and `Access` imported as `__FieldInfo` and `__Access`.
+    pub fn info(&self) -> TokenStream {
+        let name = self.ident.unraw().to_string();
+        let FieldRange { start, end, .. } = &self.range;
+        let range = match self.range.known {
+            Some(_) => quote! { #start..#end },
+            None => quote! { (#start)..(#end) },
+        };
+        let access = match self.access {
+            Access::ReadWrite => quote! { ReadWrite },
+            Access::ReadOnly => quote! { ReadOnly },
+            Access::WriteOnly => quote! { WriteOnly },
+            Access::WriteOneToClear => quote! { WriteOneToClear },
+        };
+        quote! {
+            __FieldInfo {
+                name: #name,
+                range: #range,
+                access: __Access::#access,
+            }
+        }
+    }
+
+    /// Type stored into the field, which unwraps `Option` for `FieldValue` types.
+    pub fn value_type(&self) -> &Type {
+        option_type(&self.ty).unwrap_or(&self.ty)
+    }
+
+    pub fn return_type(&self) -> &Type {
+        if let Some(pipe) = &self.pipe {
+            if let ReturnType::Type(_, ty) = &pipe.decode.output {
+                return ty;
+            }
+        }
+        &self.ty
+    }
+
+    /// Type accepted by the setter, which is the decoded type if the pipe
+    /// can be reversed.
+    pub fn input_type(&self) -> &Type {
+        match &self.pipe {
+            Some(Pipe {
+                encode: Some(_), ..
+            }) => self.return_type(),
+            _ => self.value_type(),
+        }
+    }
+
+    pub fn may_overlap(&self, other: &Field) -> bool {
+        match (&self.overlap, &other.overlap) {
+            (Overlap::Any, _) | (_, Overlap::Any) => true,
+            (Overlap::Union(union), Overlap::Union(other)) => union == other,
+            _ => false,
+        }
+    }
+}
+
+/// Bits of a field, given as `start..end`, as a single `bit` or as
+/// `start; width`. Bounds may be constant `usize` expressions.
+struct FieldRange {
+    pub tokens: TokenStream,
+    pub start: TokenStream,
+    pub end: TokenStream,
+    /// Range of literal bounds, which is checked while expanding. Other
+    /// ranges are checked by const assertions.
+    pub known: Option<Range<usize>>,
+}
+
+impl Parse for FieldRange {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let expr: Expr = input.parse()?;
+        let mut tokens = expr.to_token_stream();
+
+        let (start, end, known) = match &expr {
+            Expr::Range(range) => {
+                if matches!(range.limits, RangeLimits::Closed(_)) {
+                    return Err(Error::new_spanned(
+                        range,
+                        "Bitfield expected half-open range",
+                    ));
+                }
+                let (start, end) = match (&range.start, &range.end) {
+                    (Some(start), Some(end)) => (start, end),
+                    _ => {
+                        return Err(Error::new_spanned(
+                            range,
+                            "Bitfield expected explicit bounds",
+                        ))
+                    }
+                };
+                let known = match (parse_int(start), parse_int(end)) {
+                    (Ok(start), Ok(end)) => Some(start..end),
+                    _ => None,
+                };
+                (quote! { #start }, quote! { #end }, known)
+            }
+            start if input.peek(Token![;]) => {
+                let semi: Token![;] = input.parse()?;
+                let width: Expr = input.parse()?;
+                tokens.extend(quote! { #semi #width });
+                let known = match (parse_int::<usize>(start), parse_int::<usize>(&width)) {
+                    (Ok(start), Ok(width)) => match start.checked_add(width) {
+                        Some(end) => Some(start..end),
+                        None => {
+                            return Err(Error::new_spanned(tokens, "Bitfield range is invalid"))
+                        }
+                    },
+                    _ => None,
+                };
+                (quote! { #start }, quote! { (#start) + (#width) }, known)
+            }
+            bit => {
+                let known = match parse_int::<usize>(bit) {
+                    Ok(bit) => match bit.checked_add(1) {
+                        Some(end) => Some(bit..end),
+                        None => {
+                            return Err(Error::new_spanned(tokens, "Bitfield range is invalid"))
+                        }
+                    },
+                    Err(_) => None,
+                };
+                (quote! { #bit }, quote! { (#bit) + 1 }, known)
+            }
+        };
+
+        let (start, end) = match &known {
+            Some(range) => {
+                let start = proc_macro2::Literal::usize_unsuffixed(range.start);
+                let end = proc_macro2::Literal::usize_unsuffixed(range.end);
+                (quote! { #start }, quote! { #end })
+            }
+            None => (start, end),
+        };
+
+        Ok(FieldRange {
+            tokens,
+            start,
+            end,
+            known,
+        })
+    }
+}
+
+impl ToTokens for FieldRange {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        self.tokens.to_tokens(tokens);
+    }
+}
+
+/// Joins the lines of the doc comments in `attributes`.
+fn doc_string(attributes: &[Attribute]) -> String {
+    let lines = attributes
+        .iter()
+        .filter_map(|attribute| match &attribute.meta {
+            Meta::NameValue(MetaNameValue {
+                path,
+                value:
+                    Expr::Lit(ExprLit {
+                        lit: Lit::Str(lit), ..
+                    }),
+                ..
+            }) if path.is_ident("doc") => Some(lit.value()),
+            _ => None,
+        });
+    let lines: Vec<_> = lines
+        .map(|line| line.strip_prefix(' ').map(str::to_owned).unwrap_or(line))
+        .collect();
+    lines.join("\n").trim().to_owned()
+}
+
+fn check_serde(path: &Path) -> Result<()> {
+    if cfg!(feature = "serde") {
+        Ok(())
+    } else {
+        Err(Error::new_spanned(
+            path,
+            "Bitfield serde option requires the serde feature",
+        ))
+    }
+}
+
+fn check_data_type(ty: &Type) -> Result<()> {
+    if let Type::Array(array) = ty {
+        if Primitive::of(&array.elem) == Some(Primitive::Unsigned(8)) {
+            let len: usize = parse_int(&array.len)?;
+            if len > 0 {
+                return Ok(());
+            }
+        }
+    }
+    match Primitive::of(ty) {
+        Some(Primitive::Unsigned(_)) => Ok(()),
+        _ => Err(Error::new_spanned(
+            ty,
+            "Bitfield type must be an unsigned or byte array",
+        )),
+    }
+}
+
+/// Transformation of the value read from a field, given as `=> |v| ..` or as
+/// `=> decode |v| .., encode |v| ..` to transform written values as well.
+struct Pipe {
+    pub decode: ExprClosure,
+    pub encode: Option<ExprClosure>,
+}
+
+fn parse_pipe(input: ParseStream) -> Result<Option<Pipe>> {
+    if input.parse::<Token![=>]>().is_err() {
+        return Ok(None);
+    }
+
+    let is_keyword = |input: ParseStream, keyword: &str| {
+        input.parse::<Ident>().is_ok_and(|ident| ident == keyword)
+            && (input.peek(Token![|]) || input.peek(Token![||]) || input.peek(Token![move]))
+    };
+
+    if !is_keyword(&input.fork(), "decode") {
+        return Ok(Some(Pipe {
+            decode: input.parse()?,
+            encode: None,
+        }));
+    }
+    let _: Ident = input.parse()?;
+    let decode = input.parse()?;
+
+    let fork = input.fork();
+    let encode = if fork.parse::<Token![,]>().is_ok() && is_keyword(&fork, "encode") {
+        let _: Token![,] = input.parse()?;
+        let _: Ident = input.parse()?;
+        Some(input.parse()?)
+    } else {
+        None
+    };
+
+    Ok(Some(Pipe { decode, encode }))
+}
+
+fn parse_int<N>(expr: &Expr) -> Result<N>
+where
+    N: std::str::FromStr,
+    N::Err: std::fmt::Display,
+{
+    if let Expr::Lit(expr) = expr {
+        if let Lit::Int(literal) = &expr.lit {
+            return literal.base10_parse();
+        }
+    }
+    Err(Error::new_spanned(
+        expr,
+        "Bitfield expected integer literal",
+    ))
+}
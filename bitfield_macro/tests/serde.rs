@@ -0,0 +1,77 @@
+use bitfield_macro::{bitfield, FieldValue};
+use serde::{Deserialize, Serialize};
+
+#[test]
+fn raw() {
+    bitfield! {
+        #[bitfield(serde)]
+        struct Bitfield: u16 {
+            f1: u8 @ 0..4,
+            f2: bool @ 8..9,
+        }
+    }
+    let bf = Bitfield::new(0x010A);
+    let json = serde_json::to_string(&bf).unwrap();
+    assert_eq!(json, "266");
+    let bf: Bitfield = serde_json::from_str(&json).unwrap();
+    assert_eq!(bf.data(), 0x010A);
+    assert!(serde_json::from_str::<Bitfield>("4096").is_err());
+}
+
+#[test]
+fn map() {
+    #[derive(FieldValue, Serialize, Deserialize, Debug, PartialEq)]
+    enum Mode {
+        User = 0x10,
+        Irq = 0x12,
+    }
+
+    bitfield! {
+        #[bitfield(serde = "map")]
+        struct Cpsr: u32 {
+            mode: Option<Mode> @ 0..5,
+            t: bool @ 5..6,
+            #[ro]
+            offset: i8 @ 8..12,
+            prio: [u8; 2] @ 12..16 => |v| v * 2,
+            #[wo]
+            key: u8 @ 16..20,
+        }
+    }
+    let cpsr = Cpsr::new(0x0005_3F12);
+    let json = serde_json::to_string(&cpsr).unwrap();
+    assert_eq!(
+        json,
+        r#"{"mode":"Irq","t":false,"offset":-1,"prio":[3,0],"key":5}"#
+    );
+    let cpsr: Cpsr = serde_json::from_str(&json).unwrap();
+    assert_eq!(cpsr.data(), 0x0005_3F12);
+
+    // Patterns without a variant round-trip as their bits
+    let cpsr = Cpsr::new(0x1F);
+    let json = serde_json::to_string(&cpsr).unwrap();
+    assert_eq!(
+        json,
+        r#"{"mode":31,"t":false,"offset":0,"prio":[0,0],"key":0}"#
+    );
+    let cpsr: Cpsr = serde_json::from_str(&json).unwrap();
+    assert_eq!(cpsr.data(), 0x1F);
+
+    let json = r#"{"mode":"User","t":true,"offset":7,"prio":[1,2],"key":0}"#;
+    let cpsr: Cpsr = serde_json::from_str(json).unwrap();
+    assert_eq!(cpsr.data(), 0x0000_9730);
+
+    let invalid = [
+        r#"{"mode":"User","t":true,"offset":0,"prio":[1,2],"key":16}"#,
+        r#"{"mode":"User","t":true,"offset":0,"prio":[1,2]}"#,
+        r#"{"mode":"User","t":true,"offset":8,"prio":[1,2],"key":0}"#,
+        r#"{"mode":"User","t":true,"offset":-9,"prio":[1,2],"key":0}"#,
+        r#"{"mode":"User","t":true,"offset":0,"prio":[4,2],"key":0}"#,
+        r#"{"mode":null,"t":true,"offset":0,"prio":[1,2],"key":0}"#,
+        r#"{"mode":32,"t":true,"offset":0,"prio":[1,2],"key":0}"#,
+        r#"{"mode":"User","t":true,"offset":0,"prio":[1,2],"key":0,"x":0}"#,
+    ];
+    for json in invalid {
+        assert!(serde_json::from_str::<Cpsr>(json).is_err(), "{json}");
+    }
+}
@@ -1,4 +1,5 @@
-use bitfield_macro::bitfield;
+use bitfield_macro::{bitfield, bitfield_struct, FieldValue};
+use bitfield_runtime::{ChangeValue, FieldChange, OutOfBounds};
 
 #[test]
 fn data_mask() {
@@ -51,6 +52,7 @@ fn fields() {
         struct Bitfield: u8 {
             f1: u8 @ 0..4,
             f2: u8 @ 4..8,
+            #[overlap]
             f3: u8 @ 0..8,
         }
     }
@@ -117,3 +119,849 @@ fn traits() {
     let bf = Bitfield::from(0xFF);
     assert_eq!(bf.data(), 0x0F);
 }
+
+#[test]
+fn signed() {
+    bitfield! {
+        struct Bitfield: u16 {
+            f1: i8 @ 0..5,
+            f2: i8 @ 5..13,
+            f3: i32 @ 13..16,
+        }
+    }
+    let mut bf = Bitfield::new(0);
+    bf.set_f1(-16);
+    assert_eq!(bf.f1(), -16);
+    assert_eq!(bf.data(), 0x0010);
+    bf.set_f1(15);
+    assert_eq!(bf.f1(), 15);
+    bf.set_f1(-1);
+    assert_eq!(bf.f1(), -1);
+    assert_eq!(bf.data(), 0x001F);
+    bf.set_f2(-128);
+    assert_eq!(bf.f2(), -128);
+    assert_eq!(bf.f1(), -1);
+    bf.set_f3(-4);
+    assert_eq!(bf.f3(), -4);
+    bf.set_f3(3);
+    assert_eq!(bf.f3(), 3);
+    assert_eq!(bf.data() >> 13, 0b011);
+
+    bitfield! {
+        struct Sizes: u32 {
+            f1: isize @ 0..12,
+            f2: usize @ 12..32,
+        }
+    }
+    let sizes = Sizes::new(0xFFFF_F800);
+    assert_eq!(sizes.f1(), -2048);
+    assert_eq!(sizes.f2(), 0xFFFFF);
+}
+
+#[test]
+fn enums() {
+    #[derive(FieldValue, Debug, PartialEq)]
+    enum Size {
+        B8,
+        B16,
+        B32,
+        B64,
+    }
+
+    #[derive(FieldValue, Debug, PartialEq)]
+    enum Mode {
+        User = 0x10,
+        Fiq = 0x11,
+        Irq = 0x12,
+        Supervisor = 0x13,
+    }
+
+    bitfield! {
+        struct Bitfield: u8 {
+            size: Size @ 0..2,
+            mode: Option<Mode> @ 2..7,
+        }
+    }
+    let mut bf = Bitfield::new(0);
+    assert_eq!(bf.size(), Size::B8);
+    assert_eq!(bf.mode(), None);
+    bf.set_size(Size::B64);
+    assert_eq!(bf.size(), Size::B64);
+    bf.set_mode(Mode::Irq);
+    assert_eq!(bf.mode(), Some(Mode::Irq));
+    assert_eq!(bf.data(), 0x4B);
+    bf.set_mode(Mode::Supervisor);
+    assert_eq!(bf.mode(), Some(Mode::Supervisor));
+    assert_eq!(bf.size(), Size::B64);
+}
+
+#[test]
+fn attribute() {
+    #[bitfield_struct(u16)]
+    struct Bitfield {
+        #[bits(0..4)]
+        f1: u8,
+        #[bits(4..5)]
+        f2: bool,
+        #[bits(8..12 => |v| 2 * v)]
+        f3: u8,
+    }
+    let mut bf = Bitfield::new(0xFFFF);
+    assert_eq!(bf.data(), 0x0F1F);
+    assert_eq!(bf.f1(), 0x0F);
+    assert!(bf.f2());
+    assert_eq!(bf.f3(), 0x1E);
+    bf.set_f2(false);
+    bf.set_f3(0x3);
+    assert_eq!(bf.data(), 0x030F);
+}
+
+#[test]
+fn overlap() {
+    bitfield! {
+        #[bitfield(exhaustive)]
+        struct Bitfield: u8 {
+            #[union(word)]
+            f1: u8 @ 0..8,
+            #[union(word)]
+            f2: u8 @ 0..4,
+            #[union(word)]
+            f3: u8 @ 4..8,
+        }
+    }
+    let mut bf = Bitfield::new(0xAB);
+    assert_eq!(bf.f2(), 0x0B);
+    assert_eq!(bf.f3(), 0x0A);
+    bf.set_f1(0xCD);
+    assert_eq!(bf.f2(), 0x0D);
+}
+
+#[test]
+fn wide() {
+    bitfield! {
+        struct Bitfield: u128 {
+            f1: u64 @ 0..64,
+            f2: u128 @ 64..128,
+        }
+    }
+    let mut bf = Bitfield::new(u128::MAX);
+    assert_eq!(bf.f1(), u64::MAX);
+    bf.set_f2(0x0123_4567_89AB_CDEF);
+    assert_eq!(bf.data(), 0x0123_4567_89AB_CDEF_FFFF_FFFF_FFFF_FFFF);
+}
+
+#[test]
+fn pointer_sized() {
+    const SIZE: usize = core::mem::size_of::<usize>();
+
+    bitfield! {
+        struct Bitfield: usize {
+            f1: u8 @ 0..4,
+            f2: usize @ 16..32,
+        }
+    }
+    let mut bf = Bitfield::new(usize::MAX);
+    assert_eq!(bf.data(), 0xFFFF_000F);
+    assert_eq!((bf.f1(), bf.f2()), (0xF, 0xFFFF));
+    bf.set_f2(0x1234);
+    assert_eq!(bf.byte(2), 0x34);
+    assert_eq!(bf.checked_byte(SIZE - 1), Some(0));
+    assert_eq!(bf.checked_byte(SIZE), None);
+    assert_eq!(
+        <Bitfield as bitfield_runtime::FieldValue>::BITS,
+        usize::BITS as usize
+    );
+
+    bitfield! {
+        #[bitfield(big_endian, exhaustive)]
+        struct Top: usize {
+            top: u8 @ 0..4,
+            rest: usize @ 4..usize::BITS as usize,
+        }
+    }
+    let top = Top::new(0xA << (usize::BITS - 4));
+    assert_eq!((top.top(), top.rest()), (0xA, 0));
+}
+
+#[test]
+fn arrays() {
+    bitfield! {
+        struct Little: [u8; 10] {
+            f1: u8 @ 4..12,
+            f2: i16 @ 12..24,
+            f3: u64 @ 24..80,
+        }
+    }
+    let mut bf = Little::new([0xFF; 10]);
+    assert_eq!(
+        bf.data(),
+        [0xF0, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]
+    );
+    bf.set_data([0; 10]);
+    bf.set_f1(0xAB);
+    assert_eq!(bf.data()[..2], [0xB0, 0x0A]);
+    assert_eq!(bf.f1(), 0xAB);
+    bf.set_f2(-2);
+    assert_eq!(bf.f2(), -2);
+    assert_eq!(bf.data()[..3], [0xB0, 0xEA, 0xFF]);
+    bf.set_f3(u64::MAX);
+    assert_eq!(bf.byte(9), 0xFF);
+
+    bitfield! {
+        #[bitfield(big_endian)]
+        struct Big: [u8; 3] {
+            version: u8 @ 0..4,
+            length: u16 @ 4..16,
+            flag: bool @ 16..17,
+        }
+    }
+    let mut bf = Big::new([0x4A, 0xBC, 0x80]);
+    assert_eq!(bf.version(), 0x4);
+    assert_eq!(bf.length(), 0xABC);
+    assert!(bf.flag());
+    bf.set_length(0x123);
+    bf.set_flag(false);
+    assert_eq!(bf.data(), [0x41, 0x23, 0x00]);
+    assert_eq!(Big::data_mask(), [0xFF, 0xFF, 0x80]);
+}
+
+#[test]
+fn constant() {
+    bitfield! {
+        struct Bitfield: u16 {
+            f1: u8 @ 0..4,
+            f2: bool @ 4..5,
+            f3: i8 @ 8..16,
+        }
+    }
+    const RESET: Bitfield = Bitfield::new(0).with_f1(0xA).with_f2(true).with_f3(-1);
+    const F3: i8 = RESET.f3();
+    static BYTE: u8 = RESET.byte(0);
+    assert_eq!(RESET.data(), 0xFF1A);
+    assert_eq!(F3, -1);
+    assert_eq!(BYTE, 0x1A);
+
+    bitfield! {
+        struct Bytes: [u8; 2] {
+            f1: u16 @ 4..12,
+        }
+    }
+    const BYTES: Bytes = Bytes::new([0; 2]).with_f1(0xAB);
+    assert_eq!(BYTES.data(), [0xB0, 0x0A]);
+}
+
+#[test]
+fn builder() {
+    bitfield! {
+        #[bitfield(builder)]
+        struct Bitfield: u16 {
+            f1: u8 @ 0..4,
+            f2: bool @ 4..5,
+            #[overlap]
+            f3: u8 @ 0..8,
+        }
+    }
+    const BF: Bitfield = Bitfield::builder().f2(true).f1(0x3).build_complete();
+    assert_eq!(BF.data(), 0x13);
+    let bf = Bitfield::builder().f3(0xFF).build();
+    assert_eq!(bf.data(), 0xFF);
+    let bf = Bitfield::builder()
+        .f1(0x5)
+        .f3(0x20)
+        .f2(false)
+        .build_complete();
+    assert_eq!(bf.data(), 0x20);
+}
+
+#[test]
+fn derives() {
+    bitfield! {
+        #[derive(Default, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+        struct Bitfield: u8 {
+            f1: u8 @ 0..4,
+        }
+    }
+    let bf = Bitfield::new(0x0A);
+    let copy = bf;
+    assert_eq!(bf, copy);
+    assert_ne!(bf, Bitfield::default());
+    assert_eq!(format!("{bf:?}"), "Bitfield { data: 10 }");
+
+    bitfield! {
+        #[bitfield(debug)]
+        #[derive(Clone, Copy)]
+        struct Cpsr: u32 {
+            mode: u8 @ 0..5,
+            t: bool @ 5..6,
+            n: bool @ 31..32,
+            offset: i8 @ 8..12,
+        }
+    }
+    let cpsr = Cpsr::new(0x8000_0F13);
+    assert_eq!(
+        format!("{cpsr:?}"),
+        "Cpsr { mode: 0x13, t: false, n: true, offset: -1 }"
+    );
+}
+
+#[test]
+fn access() {
+    bitfield! {
+        struct Bitfield: u16 {
+            f1: u8 @ 0..4,
+            #[ro]
+            f2: u8 @ 4..8,
+            #[wo]
+            f3: u8 @ 8..12,
+            #[w1c]
+            f4: bool @ 12..13,
+        }
+    }
+    assert_eq!(Bitfield::writable_mask(), 0x0F0F);
+    assert_eq!(Bitfield::clear_mask(), 0x1000);
+    let mut bf = Bitfield::new(0x10A5);
+    assert_eq!(bf.f2(), 0xA);
+    assert!(bf.f4());
+    bf.set_data(0x0FFF);
+    assert_eq!(bf.data(), 0x1FAF);
+    bf.set_byte(1, 0x03);
+    assert_eq!(bf.data(), 0x13AF);
+    bf.set_data(0x1000);
+    assert_eq!(bf.data(), 0x00A0);
+    bf.set_f3(0x5);
+    assert_eq!(bf.data(), 0x05A0);
+    let mut bf = Bitfield::new(0x1000);
+    bf.clear_f4();
+    assert!(!bf.f4());
+}
+
+#[test]
+fn array_fields() {
+    bitfield! {
+        #[bitfield(builder, debug)]
+        struct Bitfield: u32 {
+            prio: [u8; 4] @ 0..16,
+            flags: [bool; 2] @ 16..18,
+        }
+    }
+    let mut bf = Bitfield::new(0x0002_4321);
+    assert_eq!(bf.prio(0), 0x1);
+    assert_eq!(bf.prio(3), 0x4);
+    assert!(!bf.flags(0));
+    assert!(bf.flags(1));
+    bf.set_prio(2, 0xF);
+    assert_eq!(bf.data(), 0x0002_4F21);
+    assert_eq!(bf.prio_iter().collect::<Vec<_>>(), [0x1, 0x2, 0xF, 0x4]);
+    assert_eq!(
+        format!("{bf:?}"),
+        "Bitfield { prio: [0x1, 0x2, 0xf, 0x4], flags: [false, true] }"
+    );
+
+    const BF: Bitfield = Bitfield::builder()
+        .prio([1, 2, 3, 4])
+        .flags([true, false])
+        .build_complete();
+    assert_eq!(BF.data(), 0x0001_4321);
+
+    bitfield! {
+        #[bitfield(big_endian)]
+        struct Palette: [u8; 2] {
+            entries: [u8; 4] @ 0..16,
+        }
+    }
+    let palette = Palette::new([0x12, 0x34]);
+    assert_eq!(palette.entries_iter().collect::<Vec<_>>(), [1, 2, 3, 4]);
+}
+
+#[test]
+#[should_panic]
+fn array_fields_bounds() {
+    bitfield! {
+        struct Bitfield: u8 {
+            prio: [u8; 2] @ 0..8,
+        }
+    }
+    Bitfield::new(0).prio(2);
+}
+
+#[test]
+fn nested() {
+    bitfield! {
+        struct Flags: u8 {
+            carry: bool @ 0..1,
+            zero: bool @ 1..2,
+            mode: u8 @ 4..8,
+        }
+    }
+
+    bitfield! {
+        struct Status: u16 {
+            code: u8 @ 0..8,
+            flags: Flags @ 8..16,
+        }
+    }
+
+    bitfield! {
+        struct Header: [u8; 3] {
+            status: Status @ 0..16,
+            tail: u8 @ 16..24,
+        }
+    }
+
+    let mut status = Status::new(0x3142);
+    assert_eq!(status.flags().mode(), 0x3);
+    assert!(status.flags().carry());
+    status.set_flags(status.flags().with_zero(true).with_mode(0xA));
+    assert_eq!(status.data(), 0xA342);
+
+    let mut header = Header::new([0; 3]);
+    header.set_status(status);
+    assert_eq!(header.data(), [0x42, 0xA3, 0x00]);
+    assert_eq!(header.status().flags().mode(), 0xA);
+}
+
+#[test]
+fn operators() {
+    bitfield! {
+        #[derive(Clone, Copy)]
+        struct Bitfield: u16 {
+            f1: u8 @ 0..4,
+            irq_enable: bool @ 4..5,
+            f3: u8 @ 8..12,
+        }
+    }
+    assert_eq!(Bitfield::F1_MASK, 0x000F);
+    assert_eq!(Bitfield::IRQ_ENABLE_MASK, 0x0010);
+    assert_eq!(Bitfield::IRQ_ENABLE_SHIFT, 4);
+    assert_eq!(Bitfield::F3_SHIFT, 8);
+    assert_eq!(Bitfield::F3_BITS, 4);
+
+    let a = Bitfield::new(0x0A1C);
+    let b = Bitfield::new(Bitfield::F1_MASK | Bitfield::F3_MASK);
+    assert_eq!((a & b).data(), 0x0A0C);
+    assert_eq!((a | b).data(), 0x0F1F);
+    assert_eq!((a ^ b).data(), 0x0513);
+    assert_eq!((!a).data(), 0x0503);
+
+    bitfield! {
+        #[derive(Clone, Copy)]
+        struct Bytes: [u8; 2] {
+            f1: u8 @ 4..12,
+        }
+    }
+    assert_eq!(Bytes::F1_MASK, [0xF0, 0x0F]);
+    assert_eq!((!Bytes::new([0x10, 0x01])).data(), [0xE0, 0x0E]);
+    let a = Bytes::new([0x30, 0x03]);
+    let b = Bytes::new([0x50, 0x05]);
+    assert_eq!((a & b).data(), [0x10, 0x01]);
+    assert_eq!((a | b).data(), [0x70, 0x07]);
+    assert_eq!((a ^ b).data(), [0x60, 0x06]);
+}
+
+#[test]
+fn const_ranges() {
+    const FLAG_BIT: usize = 3;
+    const WIDTH: usize = 4;
+
+    bitfield! {
+        #[bitfield(exhaustive)]
+        struct Bitfield: u16 {
+            f1: u8 @ 0..FLAG_BIT,
+            f2: bool @ FLAG_BIT,
+            f3: i8 @ FLAG_BIT + 1; WIDTH,
+            f4: [u8; 2] @ 8..8 + 2 * WIDTH,
+        }
+    }
+    assert_eq!(Bitfield::F2_MASK, 0x0008);
+    assert_eq!(Bitfield::F3_SHIFT, 4);
+    assert_eq!(Bitfield::F4_BITS, 8);
+    assert_eq!(Bitfield::data_mask(), 0xFFFF);
+    let mut bf = Bitfield::new(0xA5F8);
+    assert!(bf.f2());
+    assert_eq!(bf.f3(), -1);
+    assert_eq!(bf.f4(1), 0xA);
+    bf.set_f3(3);
+    bf.set_f4(0, 0xC);
+    assert_eq!(bf.data(), 0xAC38);
+
+    bitfield! {
+        #[bitfield(big_endian)]
+        struct Bytes: [u8; 2] {
+            f1: u8 @ 0; WIDTH,
+            f2: bool @ WIDTH,
+            f3: u8 @ 7..7 + 3,
+        }
+    }
+    assert_eq!(Bytes::data_mask(), [0xF9, 0xC0]);
+    let bf = Bytes::new([0xFF; 2])
+        .with_f1(0x5)
+        .with_f2(false)
+        .with_f3(0x2);
+    assert_eq!(bf.data(), [0x50, 0x80]);
+    assert_eq!((bf.f1(), bf.f2(), bf.f3()), (0x5, false, 0x2));
+}
+
+#[test]
+fn pipes_encode() {
+    bitfield! {
+        #[bitfield(builder)]
+        struct Bitfield: u16 {
+            f1: u8 @ 0..2 => decode |v| 2 * v, encode |v| v / 2,
+            f2: u8 @ 2..6 => decode |v| -> u32 { 1 << v }, encode |v: u32| v.trailing_zeros() as u8,
+            f3: bool @ 6 => decode |v| !v, encode |v| !v,
+            f4: u8 @ 8..16,
+        }
+    }
+    let mut bf = Bitfield::new(0);
+    bf.set_f1(6);
+    bf.set_f2(0x400);
+    assert_eq!(bf.data(), 0x002B);
+    assert_eq!((bf.f1(), bf.f2(), bf.f3()), (6, 0x400, true));
+    let bf = Bitfield::builder().f3(false).f2(1).build();
+    assert_eq!(bf.data(), 0x0040);
+
+    #[bitfield_struct(u8)]
+    struct Attribute {
+        #[bits(0..4 => decode |v| v + 1, encode |v| v - 1)]
+        f1: u8,
+        #[bits(4..8)]
+        encode: u8,
+    }
+    let bf = Attribute::new(0).with_f1(16).with_encode(1);
+    assert_eq!(bf.data(), 0x1F);
+    assert_eq!(bf.f1(), 16);
+}
+
+#[test]
+fn bitfield_trait() {
+    use bitfield_runtime::{Access, FieldInfo};
+
+    fn describe<B: bitfield_runtime::Bitfield>(value: &B) -> (B::Raw, Vec<&'static str>) {
+        let names = B::FIELDS.iter().map(|field| field.name).collect();
+        (value.raw(), names)
+    }
+
+    const WIDTH: usize = 4;
+
+    bitfield! {
+        struct Bitfield: u16 {
+            f1: u8 @ 0..WIDTH,
+            #[ro]
+            f2: bool @ 4,
+            #[w1c]
+            f3: [u8; 2] @ 8; 2 * WIDTH,
+        }
+    }
+    let bf = <Bitfield as bitfield_runtime::Bitfield>::from_raw(0xFFFF);
+    assert_eq!(describe(&bf), (0xFF1F, vec!["f1", "f2", "f3"]));
+    assert_eq!(<Bitfield as bitfield_runtime::Bitfield>::MASK, 0xFF1F);
+    assert_eq!(
+        <Bitfield as bitfield_runtime::Bitfield>::FIELDS[2],
+        FieldInfo {
+            name: "f3",
+            range: 8..16,
+            access: Access::WriteOneToClear,
+        }
+    );
+
+    bitfield! {
+        struct Bytes: [u8; 2] {
+            f1: u16 @ 4..12,
+        }
+    }
+    assert_eq!(describe(&Bytes::new([0xFF; 2])), ([0xF0, 0x0F], vec!["f1"]));
+}
+
+#[test]
+fn byte_order() {
+    bitfield! {
+        struct Bitfield: u32 {
+            f1: u8 @ 0..8,
+            f2: u16 @ 12..28,
+        }
+    }
+    let bf = Bitfield::from_be_bytes([0x12, 0x34, 0x56, 0x78]);
+    assert_eq!(bf.data(), 0x0234_5078);
+    assert_eq!(bf.to_le_bytes(), [0x78, 0x50, 0x34, 0x02]);
+    assert_eq!(Bitfield::from_le_bytes(bf.to_le_bytes()).data(), bf.data());
+
+    let header = [0xFF, 0x12, 0xFF, 0xFF, 0xAA];
+    let bf = Bitfield::read_from(&header);
+    assert_eq!((bf.f1(), bf.f2()), (0xFF, 0xFFF1));
+    let mut buffer = [0xFF; 5];
+    Bitfield::new(0).with_f2(0xABCD).write_to(&mut buffer);
+    assert_eq!(buffer, [0x00, 0xDF, 0xBC, 0xFA, 0xFF]);
+
+    bitfield! {
+        #[bitfield(big_endian)]
+        struct Bytes: [u8; 3] {
+            f1: u8 @ 0..4,
+            f2: u16 @ 8..24,
+        }
+    }
+    let bf = Bytes::read_from(&[0x12, 0x34, 0x56, 0x78]);
+    assert_eq!(bf.data(), [0x10, 0x34, 0x56]);
+    assert_eq!(bf.to_le_bytes(), [0x56, 0x34, 0x10]);
+    assert_eq!(Bytes::from_le_bytes([0x56, 0x34, 0x12]).f2(), 0x3456);
+    let mut buffer = [0xFF; 3];
+    bf.write_to(&mut buffer);
+    assert_eq!(buffer, [0x1F, 0x34, 0x56]);
+}
+
+#[test]
+#[should_panic]
+fn byte_order_bounds() {
+    bitfield! {
+        struct Bitfield: u16 {
+            f1: u16 @ 0..16,
+        }
+    }
+    Bitfield::read_from(&[0]);
+}
+
+#[test]
+fn checked() {
+    bitfield! {
+        struct Bitfield: u16 {
+            prio: [u8; 2] @ 0..8,
+            #[ro]
+            f1: u8 @ 8..16,
+        }
+    }
+    let mut bf = Bitfield::new(0x1234);
+    assert_eq!(bf.checked_byte(1), Some(0x12));
+    assert_eq!(bf.checked_byte(2), None);
+    assert_eq!(bf.checked_set_byte(0, 0x56), Ok(()));
+    assert_eq!(bf.checked_set_byte(1, 0x56), Ok(()));
+    assert_eq!(bf.checked_set_byte(2, 0x56), Err(OutOfBounds));
+    assert_eq!(bf.data(), 0x1256);
+    assert_eq!(bf.checked_prio(1), Some(0x5));
+    assert_eq!(bf.checked_prio(2), None);
+    assert_eq!(bf.checked_set_prio(0, 0x1), Ok(()));
+    assert_eq!(bf.checked_set_prio(2, 0x1), Err(OutOfBounds));
+    assert_eq!(bf.data(), 0x1251);
+
+    assert!(Bitfield::try_read_from(&[0x34]).is_none());
+    let bf = Bitfield::try_read_from(&[0x34, 0x12, 0xFF]).unwrap();
+    assert_eq!(bf.data(), 0x1234);
+    let mut buffer = [0xFF];
+    assert_eq!(bf.try_write_to(&mut buffer), Err(OutOfBounds));
+    assert_eq!(buffer, [0xFF]);
+    let mut buffer = [0xFF; 2];
+    assert_eq!(bf.try_write_to(&mut buffer), Ok(()));
+    assert_eq!(buffer, [0x34, 0x12]);
+}
+
+#[test]
+fn diff() {
+    bitfield! {
+        #[bitfield(diff)]
+        struct Control: u16 {
+            irq_enable: bool @ 0,
+            mode: u8 @ 1..4,
+            prio: [u8; 2] @ 4..12,
+            offset: i8 @ 12..16,
+        }
+    }
+    let old = Control::new(0x0042);
+    let new = Control::new(0xF343);
+    assert_eq!(old.diff(&old).count(), 0);
+    let changes: Vec<_> = old.diff(&new).collect();
+    assert_eq!(
+        changes,
+        [
+            FieldChange {
+                name: "irq_enable",
+                index: None,
+                old: ChangeValue::Unsigned(0),
+                new: ChangeValue::Unsigned(1),
+            },
+            FieldChange {
+                name: "prio",
+                index: Some(1),
+                old: ChangeValue::Unsigned(0x0),
+                new: ChangeValue::Unsigned(0x3),
+            },
+            FieldChange {
+                name: "offset",
+                index: None,
+                old: ChangeValue::Signed(0),
+                new: ChangeValue::Signed(-1),
+            },
+        ]
+    );
+    assert_eq!(changes[0].to_string(), "IRQ_ENABLE 0 -> 1");
+    assert_eq!(format!("{:#}", changes[1]), "PRIO[1] 0x0 -> 0x3");
+    assert_eq!(changes[2].to_string(), "OFFSET 0 -> -1");
+    assert_eq!(format!("{:#}", changes[2]), "OFFSET 0x0 -> -0x1");
+
+    bitfield! {
+        #[bitfield(big_endian, diff)]
+        struct Bytes: [u8; 2] {
+            f1: u8 @ 0..4,
+            f2: u16 @ 4..16,
+        }
+    }
+    let changes: Vec<_> = Bytes::new([0x10, 0x00])
+        .diff(&Bytes::new([0x20, 0x01]))
+        .map(|change| change.to_string())
+        .collect();
+    assert_eq!(changes, ["F1 1 -> 2", "F2 0 -> 1"]);
+}
+
+#[test]
+fn mmio() {
+    bitfield! {
+        #[bitfield(mmio)]
+        struct Status: u32 {
+            f1: u8 @ 0..8,
+            #[ro]
+            f2: bool @ 8,
+            #[w1c]
+            f3: bool @ 9,
+        }
+    }
+    let mut memory = 0xFFFF_FF42;
+    let register = unsafe { StatusRegister::from_ptr(&mut memory) };
+    assert_eq!(register.read().data(), 0x342);
+    register.modify(|value| value.with_f1(0x24));
+    assert_eq!(unsafe { register.as_ptr().read() }, 0x124);
+    register.write(Status::new(0x200));
+    assert_eq!(memory, 0x200);
+
+    bitfield! {
+        #[bitfield(mmio, big_endian)]
+        struct Bytes: [u8; 2] {
+            f1: u8 @ 0..4,
+            #[w1c]
+            f2: bool @ 15,
+        }
+    }
+    let mut memory = [0xFF; 2];
+    let register = unsafe { BytesRegister::from_ptr(&mut memory) };
+    register.modify(|value| value.with_f1(0x5));
+    assert_eq!(memory, [0x50, 0x00]);
+}
+
+#[test]
+fn describe() {
+    bitfield! {
+        /// Interrupt status.
+        #[bitfield(describe)]
+        struct Status: u16 {
+            /// Pending interrupt,
+            /// see `mask`.
+            #[w1c]
+            pending: u8 @ 0..4,
+            /// Mask | enable.
+            mask: u8 @ 8..12,
+            #[ro]
+            ready: bool @ 15,
+        }
+    }
+    let mut markdown = String::new();
+    Status::LAYOUT.write_markdown(&mut markdown).unwrap();
+    assert_eq!(
+        markdown,
+        "## Status\n\
+         \n\
+         Interrupt status.\n\
+         \n\
+         | Bits | Field | Access | Description |\n\
+         |---|---|---|---|\n\
+         | 0..4 | pending | w1c | Pending interrupt, see `mask`. |\n\
+         | 4..8 | | | Reserved |\n\
+         | 8..12 | mask | rw | Mask \\| enable. |\n\
+         | 12..15 | | | Reserved |\n\
+         | 15..16 | ready | ro |  |\n"
+    );
+
+    let mut json = String::new();
+    Status::LAYOUT.write_json(&mut json).unwrap();
+    let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(json["bits"], 16);
+    assert_eq!(json["fields"][0]["doc"], "Pending interrupt,\nsee `mask`.");
+    assert_eq!(json["fields"][2]["access"], "ro");
+    assert_eq!(json["fields"][2]["start"], 15);
+}
+
+#[test]
+fn field_attributes() {
+    bitfield! {
+        #[bitfield(builder, debug)]
+        struct Bitfield: u8 {
+            /// Carry flag.
+            c: bool @ 0,
+            #[deprecated = "use `n`"]
+            #[w1c]
+            z: [bool; 2] @ 1..3,
+            #[inline]
+            n: bool @ 3,
+        }
+    }
+    let bf = Bitfield::builder().c(true).n(true).build_complete();
+    assert_eq!(bf.data(), 0x09);
+    let bf = Bitfield::new(0x02);
+    assert_eq!(
+        format!("{bf:?}"),
+        "Bitfield { c: false, z: [true, false], n: false }"
+    );
+}
+
+#[test]
+fn raw_identifiers() {
+    bitfield! {
+        #[bitfield(builder, debug)]
+        struct Bitfield: u16 {
+            r#type: u8 @ 0..4,
+            #[w1c]
+            r#loop: [bool; 2] @ 4..6,
+            #[union(r#mod)]
+            r#in: u8 @ 8..12,
+        }
+    }
+    let mut bf = Bitfield::new(0).with_type(0x3);
+    bf.set_in(0x5);
+    assert_eq!(bf.r#type(), 0x3);
+    assert_eq!(bf.checked_loop(1), Some(false));
+    assert_eq!(bf.loop_iter().count(), 2);
+    bf.clear_loop(0);
+    assert_eq!(Bitfield::TYPE_MASK, 0x000F);
+    assert_eq!(
+        format!("{bf:?}"),
+        "Bitfield { type: 0x3, loop: [false, false], in: 0x5 }"
+    );
+    let bf = Bitfield::builder().r#type(0x1).r#in(0x2).build();
+    assert_eq!(bf.data(), 0x0201);
+}
+
+#[test]
+fn multiple() {
+    bitfield! {
+        /// Control register.
+        #[derive(Clone, Copy, Debug, PartialEq)]
+        struct Control(pub u8) {
+            enable: bool @ 0,
+            mode: u8 @ 4..8,
+        }
+
+        #[bitfield(builder, big_endian)]
+        struct Status(pub [u8; 2]) {
+            f1: u8 @ 0..4,
+        }
+
+        struct Data: u16 {
+            value: u16 @ 0..12,
+        }
+    }
+    let mut control = Control::new(0xFF);
+    assert_eq!(control.0, 0xF1);
+    control.set_mode(0x2);
+    control.0 |= 0x0E;
+    assert_eq!(control.data(), 0x2F);
+    assert_eq!(!control, Control(0xD0));
+
+    let status = Status::builder().f1(0xA).build();
+    assert_eq!(status.0, [0xA0, 0x00]);
+    assert_eq!(Data::from_le_bytes([0xFF; 2]).value(), 0xFFF);
+}
@@ -0,0 +1,10 @@
+use bitfield_macro::bitfield_struct;
+
+#[bitfield_struct(u8)]
+struct Bitfield {
+    #[bits(0..4)]
+    f1: u8,
+    f2: u8,
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use bitfield_macro::bitfield;
+
+bitfield! {
+    struct Bitfield: i32 {
+        f1: u8 @ 0..4,
+    }
+}
+
+fn main() {}
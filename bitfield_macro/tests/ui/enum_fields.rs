@@ -0,0 +1,8 @@
+use bitfield_macro::FieldValue;
+
+#[derive(FieldValue)]
+enum Mode {
+    User(u8),
+}
+
+fn main() {}
@@ -0,0 +1,23 @@
+use bitfield_macro::{bitfield, FieldValue};
+
+#[derive(FieldValue)]
+enum Size {
+    B8,
+    B16,
+    B32,
+}
+
+#[derive(FieldValue)]
+enum Mode {
+    User = 0x10,
+    Irq = 0x12,
+}
+
+bitfield! {
+    struct Bitfield: u8 {
+        size: Size @ 0..2,
+        mode: Option<Mode> @ 2..6,
+    }
+}
+
+fn main() {}
@@ -0,0 +1,11 @@
+use bitfield_macro::bitfield;
+
+bitfield! {
+    #[bitfield(exhaustive)]
+    struct Bitfield: u8 {
+        f1: u8 @ 1..4,
+        f2: u8 @ 5..6,
+    }
+}
+
+fn main() {}
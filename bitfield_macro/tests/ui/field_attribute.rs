@@ -0,0 +1,10 @@
+use bitfield_macro::bitfield;
+
+bitfield! {
+    struct Bitfield: u8 {
+        #[unknown]
+        f1: u8 @ 0..4,
+    }
+}
+
+fn main() {}
@@ -0,0 +1,12 @@
+use bitfield_macro::bitfield;
+
+bitfield! {
+    #[bitfield(debug)]
+    struct Bitfield: u8 {
+        f1: u8 @ 0..4,
+        #[cfg(any())]
+        f2: u8 @ 4..8,
+    }
+}
+
+fn main() {}
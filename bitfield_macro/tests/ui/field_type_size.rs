@@ -0,0 +1,9 @@
+use bitfield_macro::bitfield;
+
+bitfield! {
+    struct Bitfield: u128 {
+        f1: usize @ 0..72,
+    }
+}
+
+fn main() {}
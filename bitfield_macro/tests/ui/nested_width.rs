@@ -0,0 +1,15 @@
+use bitfield_macro::bitfield;
+
+bitfield! {
+    struct Flags: u8 {
+        f1: u8 @ 0..8,
+    }
+}
+
+bitfield! {
+    struct Bitfield: u16 {
+        flags: Flags @ 0..6,
+    }
+}
+
+fn main() {}
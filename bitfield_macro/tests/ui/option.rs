@@ -0,0 +1,10 @@
+use bitfield_macro::bitfield;
+
+bitfield! {
+    #[bitfield(unknown)]
+    struct Bitfield: u8 {
+        f1: u8 @ 0..4,
+    }
+}
+
+fn main() {}
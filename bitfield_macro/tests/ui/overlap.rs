@@ -0,0 +1,14 @@
+use bitfield_macro::bitfield;
+
+bitfield! {
+    struct Bitfield: u8 {
+        f1: u8 @ 0..4,
+        f2: u8 @ 2..6,
+        #[union(a)]
+        f3: u8 @ 6..8,
+        #[union(b)]
+        f4: u8 @ 6..8,
+    }
+}
+
+fn main() {}
@@ -0,0 +1,13 @@
+use bitfield_macro::bitfield;
+
+const FLAG_BIT: usize = 2;
+
+bitfield! {
+    #[bitfield(exhaustive)]
+    struct Bitfield: u8 {
+        f1: u8 @ 0..4,
+        f2: bool @ FLAG_BIT,
+    }
+}
+
+fn main() {}
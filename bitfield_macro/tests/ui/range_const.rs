@@ -0,0 +1,11 @@
+use bitfield_macro::bitfield;
+
+const WIDTH: usize = 12;
+
+bitfield! {
+    struct Bitfield: u8 {
+        f1: u8 @ 0..WIDTH,
+    }
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use bitfield_macro::bitfield;
+
+bitfield! {
+    struct Bitfield: u16 {
+        f1: bool @ 0..2,
+    }
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use bitfield_macro::bitfield;
+
+bitfield! {
+    struct Bitfield: u8 {
+        f1: u8 @ 4..12,
+    }
+}
+
+fn main() {}